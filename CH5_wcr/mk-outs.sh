@@ -2,6 +2,7 @@
 
 ROOT="tests/inputs"
 FILES="$ROOT/empty.txt $ROOT/fox.txt $ROOT/atlamal.txt"
+UNICODE="$ROOT/unicode.txt"
 OUT_DIR="tests/expected"
 
 [[ ! -d "$OUT_DIR" ]] && mkdir -p "$OUT_DIR"
@@ -21,6 +22,15 @@ for FILE in $FILES; do
     wc -ml  $FILE > ${OUT_DIR}/${BASENAME}.ml.out
 done
 
+wc -L $ROOT/fox.txt > "$OUT_DIR/fox.txt.L.out"
+LC_ALL=C.UTF-8 wc    $UNICODE > "$OUT_DIR/unicode.txt.out"
+LC_ALL=C.UTF-8 wc -L $UNICODE > "$OUT_DIR/unicode.txt.L.out"
+LC_ALL=C.UTF-8 wc -lL $FILES $UNICODE > "$OUT_DIR/all.lL.out"
+
+# No wc equivalent, checked by hand:
+#   unicode.txt.graphemes.out      (wcr --graphemes)
+#   unicode.txt.unicode-words.out  (wcr -w --unicode-words)
+
 wc < "$ROOT/atlamal.txt" > "$OUT_DIR/atlamal.txt.stdin.out"
 
 wc      $FILES > $OUT_DIR/all.out
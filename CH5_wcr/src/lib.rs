@@ -4,6 +4,8 @@ use std::{
     fs::File,
     io::{self, BufRead, BufReader},
 };
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
@@ -35,9 +37,17 @@ pub struct Config {
     #[arg(short = 'm', long, value_name = "CHARS", action = clap::ArgAction::SetTrue)]
     chars: bool,
 
-    /// Print the length of the longest line
+    /// Show grapheme cluster count
+    #[arg(long, value_name = "GRAPHEMES", action = clap::ArgAction::SetTrue)]
+    graphemes: bool,
+
+    /// Print the display width of the longest line
     #[arg(short = 'L', long = "max-line-length", value_name = "MAX-LINE-LENGHT", action = clap::ArgAction::SetTrue)]
     max_line_length: bool,
+
+    /// Count words on Unicode word boundaries (UAX #29)
+    #[arg(long, value_name = "UNICODE_WORDS", action = clap::ArgAction::SetTrue)]
+    unicode_words: bool,
 }
 
 #[derive(Debug, PartialEq)]
@@ -46,13 +56,20 @@ pub struct FileInfo {
     num_words: usize,
     num_bytes: usize,
     num_chars: usize,
+    num_graphemes: usize,
     max_line_length: usize,
 }
 
 pub fn get_args() -> MyResult<Config> {
     let mut config = Config::parse();
 
-    if !config.lines && !config.words && !config.bytes && !config.chars && !config.max_line_length {
+    if !config.lines
+        && !config.words
+        && !config.bytes
+        && !config.chars
+        && !config.graphemes
+        && !config.max_line_length
+    {
         config.lines = true;
         config.words = true;
         config.bytes = true;
@@ -67,17 +84,20 @@ pub fn run(config: Config) -> MyResult<()> {
         num_words: 0,
         num_bytes: 0,
         num_chars: 0,
+        num_graphemes: 0,
         max_line_length: 0,
     };
     for filename in &config.files {
         match open(filename) {
             Err(e) => eprintln!("{}: {}", filename, e),
             Ok(file) => {
-                let info = count(file)?;
+                let info = count(file, config.unicode_words)?;
                 total.num_lines += info.num_lines;
                 total.num_words += info.num_words;
                 total.num_bytes += info.num_bytes;
                 total.num_chars += info.num_chars;
+                total.num_graphemes += info.num_graphemes;
+                total.max_line_length = total.max_line_length.max(info.max_line_length);
                 if config.lines {
                     print!("{:>8}", info.num_lines);
                 }
@@ -87,6 +107,9 @@ pub fn run(config: Config) -> MyResult<()> {
                 if config.chars {
                     print!("{:>8}", info.num_chars);
                 }
+                if config.graphemes {
+                    print!("{:>8}", info.num_graphemes);
+                }
                 if config.bytes {
                     print!("{:>8}", info.num_bytes);
                 }
@@ -107,11 +130,14 @@ pub fn run(config: Config) -> MyResult<()> {
         if config.chars {
             print!("{:>8}", total.num_chars);
         }
+        if config.graphemes {
+            print!("{:>8}", total.num_graphemes);
+        }
         if config.bytes {
             print!("{:>8}", total.num_bytes);
         }
         if config.max_line_length {
-            println!("{:>8}", total.max_line_length);
+            print!("{:>8}", total.max_line_length);
         }
         println!(" total");
     }
@@ -125,46 +151,95 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
-pub fn count(mut file: impl BufRead) -> MyResult<FileInfo> {
+pub fn count(mut file: impl BufRead, unicode_words: bool) -> MyResult<FileInfo> {
     let mut input = String::new();
     file.read_to_string(&mut input)?;
     let num_lines = input.lines().count();
-    let num_words = input.split_whitespace().count();
-    let num_bytes = input.bytes().count();
+    let num_words = if unicode_words {
+        input.unicode_words().count()
+    } else {
+        input.split_whitespace().count()
+    };
+    let num_bytes = input.len();
     let num_chars = input.chars().count();
-    let mut max_line_length = 0;
-    for line in input.lines() {
-        if line.chars().count() > max_line_length {
-            max_line_length = line.chars().count();
-        }
-    }
+    let num_graphemes = input.graphemes(true).count();
+    let max_line_length = input.lines().map(display_width).max().unwrap_or(0);
     Ok(FileInfo {
         num_lines,
         num_words,
         num_bytes,
         num_chars,
+        num_graphemes,
         max_line_length,
     })
 }
 
+/// Terminal width of the widest segment of `line`, the way GNU wc measures it:
+/// tabs advance to the next multiple of 8, `\r` and `\f` return to column 0,
+/// and control characters take no space.
+fn display_width(line: &str) -> usize {
+    let mut max = 0;
+    let mut col = 0;
+    for c in line.chars() {
+        match c {
+            '\t' => col += 8 - col % 8,
+            '\r' | '\x0c' => {
+                max = max.max(col);
+                col = 0;
+            }
+            _ => col += c.width().unwrap_or(0),
+        }
+    }
+    max.max(col)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{count, FileInfo};
+    use super::{count, display_width, FileInfo};
     use std::io::Cursor;
 
     #[test]
     fn test_count() {
         let text = "I don't want the world. I just want your half.\r\n";
-        let info = count(Cursor::new(text));
+        let info = count(Cursor::new(text), false);
         assert!(info.is_ok());
         let expected = FileInfo {
             num_lines: 1,
             num_words: 10,
             num_chars: 48,
             num_bytes: 48,
-            max_line_length: 48,
+            num_graphemes: 47,
+            max_line_length: 46,
         };
 
         assert_eq!(info.unwrap(), expected);
     }
+
+    #[test]
+    fn test_count_unicode() {
+        let text = "日本語のテキスト\ne\u{301}cole 👩\u{200d}👧\n";
+        let info = count(Cursor::new(text), true);
+        assert!(info.is_ok());
+        let expected = FileInfo {
+            num_lines: 2,
+            num_words: 6,
+            num_chars: 20,
+            num_bytes: 45,
+            num_graphemes: 17,
+            max_line_length: 16,
+        };
+
+        assert_eq!(info.unwrap(), expected);
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width(""), 0);
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("a\tb"), 9);
+        assert_eq!(display_width("12345678\tb"), 17);
+        assert_eq!(display_width("日本"), 4);
+        assert_eq!(display_width("e\u{301}"), 1);
+        assert_eq!(display_width("long line\rab"), 9);
+    }
 }
@@ -1,3 +1,5 @@
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
@@ -9,6 +11,7 @@ const PRG: &str = "wcr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const ATLAMAL: &str = "tests/inputs/atlamal.txt";
+const UNICODE: &str = "tests/inputs/unicode.txt";
 
 // --------------------------------------------------
 fn gen_bad_file() -> String {
@@ -116,6 +119,12 @@ fn fox_bytes_lines() -> TestResult {
     run(&["-l", "-c", FOX], "tests/expected/fox.txt.cl.out")
 }
 
+// --------------------------------------------------
+#[test]
+fn fox_max_line_length() -> TestResult {
+    run(&["-L", FOX], "tests/expected/fox.txt.L.out")
+}
+
 // --------------------------------------------------
 #[test]
 fn atlamal() -> TestResult {
@@ -212,3 +221,42 @@ fn test_all_words_lines() -> TestResult {
 fn test_all_bytes_lines() -> TestResult {
     run(&["-cl", EMPTY, FOX, ATLAMAL], "tests/expected/all.cl.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn test_all_lines_max_line_length() -> TestResult {
+    run(
+        &["-lL", EMPTY, FOX, ATLAMAL, UNICODE],
+        "tests/expected/all.lL.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn unicode() -> TestResult {
+    run(&[UNICODE], "tests/expected/unicode.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn unicode_max_line_length() -> TestResult {
+    run(&["-L", UNICODE], "tests/expected/unicode.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn unicode_graphemes() -> TestResult {
+    run(
+        &["--graphemes", UNICODE],
+        "tests/expected/unicode.txt.graphemes.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn unicode_words() -> TestResult {
+    run(
+        &["-w", "--unicode-words", UNICODE],
+        "tests/expected/unicode.txt.unicode-words.out",
+    )
+}
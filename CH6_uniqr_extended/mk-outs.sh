@@ -20,3 +20,112 @@ for FILE in $ROOT/*.txt; do
     uniq    < $FILE > ${OUT_DIR}/${BASENAME}.stdin.out
     uniq -c < $FILE > ${OUT_DIR}/${BASENAME}.stdin.c.out
 done
+
+# Selection modes, GNU uniq only (counts are trimmed to the 4-wide -c column)
+GRP="$ROOT/groups.txt"
+echo -ne "a\na\nb\nc\nC\nc\nd\ne\nE\n" > $GRP
+uniq -d                      $GRP > $OUT_DIR/groups.txt.d.out
+uniq -d -i                   $GRP > $OUT_DIR/groups.txt.d.i.out
+uniq -d -c                   $GRP | sed 's/^   //' > $OUT_DIR/groups.txt.d.c.out
+uniq -u                      $GRP > $OUT_DIR/groups.txt.u.out
+uniq -u -i                   $GRP > $OUT_DIR/groups.txt.u.i.out
+uniq -u -c                   $GRP | sed 's/^   //' > $OUT_DIR/groups.txt.u.c.out
+uniq -d -u                   $GRP > $OUT_DIR/groups.txt.d.u.out
+uniq -D                      $GRP > $OUT_DIR/groups.txt.D.out
+uniq -D -i                   $GRP > $OUT_DIR/groups.txt.D.i.out
+uniq -D -u                   $GRP > $OUT_DIR/groups.txt.D.u.out
+uniq --all-repeated=separate -u $GRP > $OUT_DIR/groups.txt.D.separate.u.out
+uniq --all-repeated=separate $GRP > $OUT_DIR/groups.txt.D.separate.out
+uniq --all-repeated=separate -i $GRP > $OUT_DIR/groups.txt.D.separate.i.out
+uniq --all-repeated=prepend  $GRP > $OUT_DIR/groups.txt.D.prepend.out
+uniq --group                 $GRP > $OUT_DIR/groups.txt.group.out
+uniq --group -i              $GRP > $OUT_DIR/groups.txt.group.i.out
+uniq --group=prepend         $GRP > $OUT_DIR/groups.txt.group.prepend.out
+uniq --group=append          $GRP > $OUT_DIR/groups.txt.group.append.out
+uniq --group=both            $GRP > $OUT_DIR/groups.txt.group.both.out
+
+# Skipping fields and characters, input is tests/inputs/skip_fields.txt
+SKIP="$ROOT/skip_fields.txt"
+uniq -f 2           $SKIP > $OUT_DIR/skip_fields.txt.f2.out
+uniq -f 2 -i        $SKIP > $OUT_DIR/skip_fields.txt.f2.i.out
+uniq -f 2 -c        $SKIP | sed 's/^   //' > $OUT_DIR/skip_fields.txt.f2.c.out
+uniq -f 2 -s 5      $SKIP > $OUT_DIR/skip_fields.txt.f2.s5.out
+uniq -f 2 -w 10 -i  $SKIP > $OUT_DIR/skip_fields.txt.f2.w10.i.out
+uniq -s 11          $SKIP > $OUT_DIR/skip_fields.txt.s11.out
+uniq -s 20 -w 4     $SKIP > $OUT_DIR/skip_fields.txt.s20.w4.out
+
+# Global dedup has no uniq equivalent, awk keeps the first occurrence instead
+GLOBAL="$ROOT/global.txt"
+awk '!s[$0]++'               $GLOBAL > $OUT_DIR/global.txt.out
+awk '!s[tolower($0)]++'      $GLOBAL > $OUT_DIR/global.txt.i.out
+awk '!s[substr($0, 1, 5)]++' $GLOBAL > $OUT_DIR/global.txt.w5.out
+awk '{ c[$0]++; if (c[$0] == 1) l[NR] = $0 }
+     END { for (i = 1; i <= NR; i++) if (i in l) printf "%4d %s\n", c[l[i]], l[i] }' \
+    $GLOBAL > $OUT_DIR/global.txt.c.out
+awk '{ c[$0]++; a[NR] = $0 }
+     END { for (i = 1; i <= NR; i++) if (c[a[i]] > 1 && !p[a[i]]++) print a[i] }' \
+    $GLOBAL > $OUT_DIR/global.txt.d.out
+awk '{ c[$0]++; a[NR] = $0 }
+     END { for (i = 1; i <= NR; i++) if (c[a[i]] == 1) print a[i] }' \
+    $GLOBAL > $OUT_DIR/global.txt.u.out
+
+# Histograms: ties keep their first appearance, which matches C sort order here
+HIST="$ROOT/histogram.txt"
+LC_ALL=C sort $HIST | uniq -c | sort -s -k1,1nr | sed 's/^   //' > $OUT_DIR/histogram.txt.out
+LC_ALL=C sort $HIST | uniq -c | sort -s -k1,1nr | sed 's/^   //' | head -n 3 > $OUT_DIR/histogram.txt.top3.out
+LC_ALL=C sort $HIST | uniq -c | sed 's/^   //' > $OUT_DIR/histogram.txt.key.out
+
+# No uniq equivalent, checked by hand:
+#   histogram.txt.i.out             (uniqr --histogram -i)
+#   histogram.txt.percent.bar.out   (uniqr --histogram --percent --bar)
+
+# NUL terminated records
+ZERO="$ROOT/zero.txt"
+printf 'a\0a\0b\nc\0b\nc\0B\nC\0d\0d\0' > $ZERO
+uniq -z                         $ZERO > $OUT_DIR/zero.txt.out
+uniq -z -c                      $ZERO | sed -z 's/^   //' > $OUT_DIR/zero.txt.c.out
+uniq -z -i                      $ZERO > $OUT_DIR/zero.txt.i.out
+uniq -z -u                      $ZERO > $OUT_DIR/zero.txt.u.out
+uniq -z --all-repeated=separate $ZERO > $OUT_DIR/zero.txt.D.separate.out
+uniq -z --group=both            $ZERO > $OUT_DIR/zero.txt.group.both.out
+
+# No uniq equivalent for --key, checked by hand:
+#   key.csv.k1.out          (uniqr --delimiter , --key 1)
+#   key.csv.k1-2.i.out      (uniqr --delimiter , --key 1-2 -i)
+#   key.csv.k2,4.c.out      (uniqr --delimiter , --key 2,4 -c)
+#   key.csv.k1,4.global.out (uniqr --delimiter , --key 1,4 --global)
+#   key.tsv.k2.i.out        (uniqr --key 2 -i)
+
+# --key with awk, the way uniq would compare the fields
+awk -F, '{ k = $1 "," $2 } NR == 1 || k != prev { print } { prev = k }' \
+    $ROOT/key.csv > $OUT_DIR/key.csv.k-2.out
+
+# blanks at the end of a line belong to the last field
+TRAILING="$ROOT/trailing.txt"
+printf 'x 1 \ny 1\ny 1\nz 1  \nw 1  \n' > $TRAILING
+uniq -f 1 $TRAILING > $OUT_DIR/trailing.txt.f1.out
+uniq -s 2 $TRAILING > $OUT_DIR/trailing.txt.s2.out
+TRAILING_CSV="$ROOT/trailing.csv"
+printf 'a,1,\nb,1, \nc,1, \nd,1,\ne,1,\n' > $TRAILING_CSV
+awk -F, 'NR == 1 || $3 != prev { print } { prev = $3 }' \
+    $TRAILING_CSV > $OUT_DIR/trailing.csv.k3.out
+awk -F, '{ k = substr($0, index($0, ",") + 1) } NR == 1 || k != prev { print } { prev = k }' \
+    $TRAILING_CSV > $OUT_DIR/trailing.csv.k2-.out
+# whole lines compare without their trailing blanks or CR
+BLANKS="$ROOT/blanks.txt"
+printf 'a\na \na\t\nb\r\nb\nc  \nc\r\nd\n' > $BLANKS
+awk '{ k = $0; sub(/[ \t\r]+$/, "", k) } NR == 1 || k != prev { print } { prev = k }' \
+    $BLANKS > $OUT_DIR/blanks.txt.trimmed.out
+awk '{ k = $0; sub(/[ \t\r]+$/, "", k) }
+     NR > 1 && k != prev { printf "%4d %s\n", n, first; n = 0 }
+     n++ == 0 { first = $0 } { prev = k }
+     END { if (n) printf "%4d %s\n", n, first }' \
+    $BLANKS > $OUT_DIR/blanks.txt.trimmed.c.out
+
+# Several inputs are read as one stream
+cat $GRP $HIST | uniq > $OUT_DIR/groups.histogram.out
+
+# an input without a final newline still ends its last line
+NO_NEWLINE="$ROOT/no_newline.txt"
+printf 'x\ny' > $NO_NEWLINE
+{ cat $NO_NEWLINE; echo; cat $GRP; } | uniq > $OUT_DIR/no_newline.groups.out
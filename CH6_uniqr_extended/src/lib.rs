@@ -1,12 +1,21 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use std::{
+    cmp::Reverse,
+    collections::{hash_map::DefaultHasher, BinaryHeap, HashMap},
     error::Error,
-    fs::File,
-    io::{self, BufRead, BufReader, Write},
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    mem,
+    ops::Range,
+    path::Path,
 };
+use tempfile::NamedTempFile;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+type PositionList = Vec<Range<usize>>;
+
 /// report or filter out repeated lines in a file
 #[derive(Parser, Debug)]
 #[command(
@@ -16,12 +25,12 @@ type MyResult<T> = Result<T, Box<dyn Error>>;
     author = "huzwares <huzwares@skiff.com>"
 )]
 pub struct Config {
-    /// Input file
-    #[arg(value_name = "IN_FILE", default_value = "-")]
-    in_file: String,
+    /// Input files read as one stream, followed by the output file when more
+    /// than one is given ("-" for stdout)
+    #[arg(value_name = "FILES", default_value = "-")]
+    in_files: Vec<String>,
 
-    /// Output file
-    #[arg(value_name = "OUT_FILE", default_value = None)]
+    #[arg(skip)]
     out_file: Option<String>,
 
     /// Show counts
@@ -35,44 +44,624 @@ pub struct Config {
     /// compare no more than N character in lines
     #[arg(short = 'w', long, value_name = "CHECK_CHARS", default_value = None, action = clap::ArgAction::Set)]
     check_chars: Option<usize>,
+
+    /// avoid comparing the first N fields
+    #[arg(short = 'f', long, value_name = "N", default_value = "0")]
+    skip_fields: usize,
+
+    /// avoid comparing the first N characters
+    #[arg(short = 's', long, value_name = "N", default_value = "0")]
+    skip_chars: usize,
+
+    /// only print duplicate lines, one for each group
+    #[arg(short = 'd', long, value_name = "REPEATED", action = clap::ArgAction::SetTrue)]
+    repeated: bool,
+
+    /// print all duplicate lines, delimiting groups with METHOD
+    #[arg(
+        short = 'D',
+        long = "all-repeated",
+        value_name = "METHOD",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "none",
+        conflicts_with = "count"
+    )]
+    all_repeated: Option<Delimit>,
+
+    /// show all items, separating groups with an empty line placed by METHOD
+    #[arg(
+        long,
+        value_name = "METHOD",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "separate",
+        conflicts_with_all = ["count", "repeated", "all_repeated", "unique"]
+    )]
+    group: Option<Grouping>,
+
+    /// only print unique lines
+    #[arg(short = 'u', long, value_name = "UNIQUE", action = clap::ArgAction::SetTrue)]
+    unique: bool,
+
+    /// line delimiter is NUL, not newline
+    #[arg(short = 'z', long, value_name = "ZERO_TERMINATED", action = clap::ArgAction::SetTrue)]
+    zero_terminated: bool,
+
+    /// compare only the selected fields, e.g. 2,4-5 or 3-
+    #[arg(long, value_name = "LIST", value_parser = parse_pos, conflicts_with = "skip_fields", allow_hyphen_values = true)]
+    key: Option<PositionList>,
+
+    /// field delimiter for --key
+    #[arg(long, value_name = "DELIMITER", default_value = "\t", requires = "key")]
+    delimiter: char,
+
+    /// drop repeated lines anywhere in the input, keeping the first one
+    #[arg(long, value_name = "GLOBAL", action = clap::ArgAction::SetTrue, conflicts_with_all = ["all_repeated", "group"])]
+    global: bool,
+
+    /// spill --global keys to disk once they take more than SIZE bytes (K, M, G suffixes)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, requires = "global")]
+    max_memory: Option<usize>,
+
+    /// count every line over the whole input, most frequent first
+    #[arg(
+        long,
+        value_name = "HISTOGRAM",
+        action = clap::ArgAction::SetTrue,
+        conflicts_with_all = ["global", "max_memory", "all_repeated", "group", "repeated", "unique"]
+    )]
+    histogram: bool,
+
+    /// order of the --histogram output
+    #[arg(
+        long,
+        value_name = "ORDER",
+        value_enum,
+        default_value = "count",
+        requires = "histogram"
+    )]
+    sort: Order,
+
+    /// only print the first N --histogram entries
+    #[arg(long, value_name = "N", requires = "histogram")]
+    top: Option<usize>,
+
+    /// add a percentage column to the --histogram output
+    #[arg(long, value_name = "PERCENT", action = clap::ArgAction::SetTrue, requires = "histogram")]
+    percent: bool,
+
+    /// add a bar chart column to the --histogram output
+    #[arg(long, value_name = "BAR", action = clap::ArgAction::SetTrue, requires = "histogram")]
+    bar: bool,
+}
+
+/// where `--all-repeated` puts the empty line between duplicate groups
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delimit {
+    None,
+    Prepend,
+    Separate,
+}
+
+/// how `--histogram` orders its entries
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    Count,
+    Key,
+}
+
+/// where `--group` puts the empty line around groups
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Grouping {
+    Separate,
+    Prepend,
+    Append,
+    Both,
 }
 
 pub fn get_args() -> MyResult<Config> {
-    let conf = Config::parse();
+    let mut conf = Config::parse();
+    if conf.histogram {
+        conf.count = true;
+    }
+    if conf.in_files.len() > 1 {
+        conf.out_file = conf.in_files.pop().filter(|path| path != "-");
+    }
 
     Ok(conf)
 }
 
 pub fn run(conf: Config) -> MyResult<()> {
-    let mut file = open(&conf.in_file).map_err(|e| format!("{}: {}", conf.in_file, e))?;
-    let mut out_file: Box<dyn Write> = match &conf.out_file {
-        Some(path) => Box::new(File::create(path)?),
-        _ => Box::new(io::stdout()),
-    };
-    let mut print = |count: u64, text: &str| -> MyResult<()> {
-        if count > 0 {
-            if conf.count {
-                write!(out_file, "{:>4} {}", count, text)?;
+    let terminator = if conf.zero_terminated { b'\0' } else { b'\n' };
+    let mut input: Box<dyn Read> = Box::new(io::empty());
+    for (i, filename) in conf.in_files.iter().enumerate() {
+        let file = open(filename).map_err(|e| format!("{}: {}", filename, e))?;
+        input = if i + 1 < conf.in_files.len() {
+            Box::new(input.chain(Terminated::new(file, terminator)))
+        } else {
+            Box::new(input.chain(file))
+        };
+    }
+    let file = BufReader::new(input);
+    match &conf.out_file {
+        None => uniq(file, io::stdout(), &conf),
+        Some(path) => {
+            for filename in &conf.in_files {
+                if same_file(filename, path) {
+                    return Err(From::from(format!("{}: input file is output file", path)));
+                }
+            }
+            // write next to OUT_FILE and only replace it once everything succeeded
+            let dir = match Path::new(path).parent() {
+                Some(dir) if dir != Path::new("") => dir,
+                _ => Path::new("."),
+            };
+            let mut temp = NamedTempFile::new_in(dir).map_err(|e| format!("{}: {}", path, e))?;
+            let mut out_file = BufWriter::new(&mut temp);
+            uniq(file, &mut out_file, &conf)?;
+            out_file.flush()?;
+            drop(out_file);
+            set_permissions(temp.as_file(), path)?;
+            temp.persist(path).map_err(|e| format!("{}: {}", path, e))?;
+            Ok(())
+        }
+    }
+}
+
+/// an input that ends with the record terminator even when the file does
+/// not, so that its last line stays apart from the first of the next file
+struct Terminated<R> {
+    inner: R,
+    terminator: u8,
+    last: Option<u8>,
+    done: bool,
+}
+
+impl<R> Terminated<R> {
+    fn new(inner: R, terminator: u8) -> Self {
+        Terminated {
+            inner,
+            terminator,
+            last: None,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Read for Terminated<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.last = Some(buf[n - 1]);
+            return Ok(n);
+        }
+        self.done = true;
+        match self.last {
+            Some(last) if last != self.terminator => {
+                buf[0] = self.terminator;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+fn uniq(mut file: impl BufRead, mut out_file: impl Write, conf: &Config) -> MyResult<()> {
+    let terminator = if conf.zero_terminated { b'\0' } else { b'\n' };
+    let mut groups: u64 = 0;
+    let mut print = |count: u64, group: &[String]| -> MyResult<()> {
+        if count == 0 {
+            return Ok(());
+        }
+        if let Some(method) = conf.group {
+            if groups > 0 || matches!(method, Grouping::Prepend | Grouping::Both) {
+                out_file.write_all(&[terminator])?;
+            }
+            for text in group {
+                write!(out_file, "{}", text)?;
+            }
+        } else if let Some(method) = conf.all_repeated {
+            if count == 1 {
+                return Ok(());
+            }
+            if (groups > 0 && method == Delimit::Separate) || method == Delimit::Prepend {
+                out_file.write_all(&[terminator])?;
+            }
+            // as GNU uniq does, -u drops the last line of each group
+            let group = if conf.unique {
+                &group[..group.len() - 1]
             } else {
+                group
+            };
+            for text in group {
                 write!(out_file, "{}", text)?;
             }
+        } else if (conf.repeated && count == 1) || (conf.unique && count > 1) {
+            return Ok(());
+        } else if conf.count {
+            write!(out_file, "{:>4} {}", count, group[0])?;
+        } else {
+            write!(out_file, "{}", group[0])?;
         }
+        groups += 1;
         Ok(())
     };
+    if conf.histogram {
+        return histogram(file, conf, &mut print);
+    }
+    if conf.global {
+        return global(file, conf, &mut print);
+    }
+    // -D and --group print every member of a group, the other modes only the first
+    let keep_group = conf.all_repeated.is_some() || conf.group.is_some();
     let mut line = String::new();
-    let mut previous = String::new();
+    let mut group: Vec<String> = Vec::new();
     let mut count: u64 = 0;
-    while file.read_line(&mut line)? != 0 {
-        if check(&line, &previous, &conf.ignore_case, &conf.check_chars) {
-            print(count, &previous)?;
-            previous = line.clone();
+    while read_line(&mut file, &mut line, conf.zero_terminated)? != 0 {
+        if group
+            .first()
+            .is_none_or(|previous| check(&line, previous, conf))
+        {
+            print(count, &group)?;
+            group.clear();
             count = 0;
         }
+        if group.is_empty() || keep_group {
+            group.push(line.clone());
+        }
 
         count += 1;
         line.clear();
     }
-    print(count, &previous)?;
+    print(count, &group)?;
+    if groups > 0 && matches!(conf.group, Some(Grouping::Append | Grouping::Both)) {
+        out_file.write_all(&[terminator])?;
+    }
+    Ok(())
+}
+
+/// bytes the allocator adds to each heap block, roughly
+const ALLOC_OVERHEAD: usize = 16;
+
+/// number of temp files keys are hashed into once --global spills to disk
+const SPILL_BUCKETS: u64 = 16;
+
+/// how many times a spilled bucket over --max-memory is hashed again
+const MAX_SPILL_DEPTH: u64 = 8;
+
+/// one input line as stored in the --global spill files
+struct Record {
+    index: u64,
+    count: u64,
+    key: String,
+    line: String,
+}
+
+/// the records of a spilled bucket, in input order
+type Records = Box<dyn Iterator<Item = MyResult<Record>>>;
+
+/// rough memory a new --global key takes: its hash map slot, with the room
+/// the map keeps spare, and its text on the heap; when counting, also the
+/// Record holding the line and another copy of the key
+fn entry_cost(key: &str, line: Option<&str>) -> usize {
+    let slot = (mem::size_of::<(String, usize)>() + 1) * 8 / 7;
+    let mut cost = slot + ALLOC_OVERHEAD + key.len();
+    if let Some(line) = line {
+        cost += mem::size_of::<Record>() + 2 * ALLOC_OVERHEAD + key.len() + line.len();
+    }
+    cost
+}
+
+/// keep the first line of each key over the whole input, in input order; past
+/// --max-memory new keys are hashed into temp files and merged back by position
+fn global(
+    mut file: impl BufRead,
+    conf: &Config,
+    print: &mut impl FnMut(u64, &[String]) -> MyResult<()>,
+) -> MyResult<()> {
+    // counts are only known at the end of the input, otherwise print as we go
+    let counting = conf.count || conf.repeated || conf.unique || conf.histogram;
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut kept: Vec<Record> = Vec::new();
+    let mut memory = 0;
+    let mut buckets: Vec<BufWriter<File>> = Vec::new();
+    let mut line = String::new();
+    let mut index: u64 = 0;
+    while read_line(&mut file, &mut line, conf.zero_terminated)? != 0 {
+        let key = key(&line, conf);
+        if let Some(&slot) = seen.get(&key) {
+            if counting {
+                kept[slot].count += 1;
+            }
+        } else if !buckets.is_empty() {
+            let bucket = (hash(&key, 0) % SPILL_BUCKETS) as usize;
+            write_record(&mut buckets[bucket], index, 1, &key, &line)?;
+        } else {
+            memory += entry_cost(&key, counting.then_some(line.as_str()));
+            if counting {
+                kept.push(Record {
+                    index,
+                    count: 1,
+                    key: key.clone(),
+                    line: line.clone(),
+                });
+            } else {
+                print(1, &[line.clone()])?;
+            }
+            seen.insert(key, kept.len().saturating_sub(1));
+            if conf.max_memory.is_some_and(|max| memory > max) {
+                buckets = new_buckets()?;
+            }
+        }
+
+        index += 1;
+        line.clear();
+    }
+    drop(seen);
+
+    // every source below is ordered by the index of the first occurrence
+    let mut sources: Vec<Records> = vec![Box::new(kept.into_iter().map(Ok))];
+    let max_memory = conf.max_memory.unwrap_or(usize::MAX);
+    for bucket in buckets {
+        sources.push(first_occurrences(bucket, max_memory, 0)?);
+    }
+    merge(sources, |record| print(record.count, &[record.line]))
+}
+
+/// go through the records of all sources, each ordered by index, by index
+fn merge(mut sources: Vec<Records>, mut each: impl FnMut(Record) -> MyResult<()>) -> MyResult<()> {
+    let mut heads = BinaryHeap::new();
+    let mut pending: Vec<Option<Record>> = Vec::with_capacity(sources.len());
+    for (source, records) in sources.iter_mut().enumerate() {
+        let record = records.next().transpose()?;
+        if let Some(record) = &record {
+            heads.push(Reverse((record.index, source)));
+        }
+        pending.push(record);
+    }
+    while let Some(Reverse((_, source))) = heads.pop() {
+        if let Some(record) = pending[source].take() {
+            each(record)?;
+        }
+        if let Some(record) = sources[source].next().transpose()? {
+            heads.push(Reverse((record.index, source)));
+            pending[source] = Some(record);
+        }
+    }
+    Ok(())
+}
+
+/// width of the --bar column for the most frequent key
+const BAR_WIDTH: usize = 30;
+
+/// count keys like --global, then sort the counts and decorate the lines
+fn histogram(
+    file: impl BufRead,
+    conf: &Config,
+    print: &mut impl FnMut(u64, &[String]) -> MyResult<()>,
+) -> MyResult<()> {
+    let mut entries: Vec<(u64, String)> = Vec::new();
+    global(file, conf, &mut |count: u64, group: &[String]| {
+        entries.push((count, group[0].clone()));
+        Ok(())
+    })?;
+    let total: u64 = entries.iter().map(|(count, _)| count).sum();
+    let max = entries.iter().map(|(count, _)| *count).max().unwrap_or(0);
+    // stable sorts, so ties stay in order of first appearance
+    match conf.sort {
+        Order::Count => entries.sort_by_key(|(count, _)| Reverse(*count)),
+        Order::Key => entries.sort_by_cached_key(|(_, line)| key(line, conf)),
+    }
+    entries.truncate(conf.top.unwrap_or(usize::MAX));
+    for (count, line) in entries {
+        let mut text = String::new();
+        if conf.percent {
+            text += &format!("{:>6.2}% ", count as f64 * 100.0 / total as f64);
+        }
+        if conf.bar {
+            let width = (count as usize * BAR_WIDTH).div_ceil(max as usize);
+            text += &format!("{:<width$} ", "#".repeat(width), width = BAR_WIDTH);
+        }
+        text += &line;
+        print(count, &[text])?;
+    }
+    Ok(())
+}
+
+fn new_buckets() -> io::Result<Vec<BufWriter<File>>> {
+    (0..SPILL_BUCKETS)
+        .map(|_| Ok(BufWriter::new(tempfile::tempfile()?)))
+        .collect()
+}
+
+/// rewind a spill file written through `out` for reading
+fn reopen(out: BufWriter<File>) -> io::Result<BufReader<File>> {
+    let mut file = out.into_inner().map_err(|e| e.into_error())?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(BufReader::new(file))
+}
+
+fn records(mut input: BufReader<File>) -> Records {
+    Box::new(std::iter::from_fn(move || {
+        read_record(&mut input).transpose()
+    }))
+}
+
+/// collapse a spilled bucket to the first occurrence of each key, with its
+/// count; a bucket with more keys than fit in --max-memory is hashed again
+/// with another seed into smaller ones first
+fn first_occurrences(bucket: BufWriter<File>, max_memory: usize, depth: u64) -> MyResult<Records> {
+    let mut input = reopen(bucket)?;
+    let mut slots: HashMap<String, usize> = HashMap::new();
+    let mut firsts: Vec<Record> = Vec::new();
+    let mut memory = 0;
+    while let Some(record) = read_record(&mut input)? {
+        match slots.get(&record.key) {
+            Some(&slot) => firsts[slot].count += record.count,
+            None => {
+                memory += entry_cost(&record.key, Some(&record.line));
+                if memory > max_memory && !firsts.is_empty() && depth < MAX_SPILL_DEPTH {
+                    drop((slots, firsts));
+                    return split(input, max_memory, depth + 1);
+                }
+                slots.insert(record.key.clone(), firsts.len());
+                firsts.push(record);
+            }
+        }
+    }
+    drop(slots);
+    let mut out = BufWriter::new(tempfile::tempfile()?);
+    for record in &firsts {
+        write_record(
+            &mut out,
+            record.index,
+            record.count,
+            &record.key,
+            &record.line,
+        )?;
+    }
+    Ok(records(reopen(out)?))
+}
+
+/// hash the records of a bucket into new ones and collapse each of those
+fn split(mut input: BufReader<File>, max_memory: usize, depth: u64) -> MyResult<Records> {
+    input.seek(SeekFrom::Start(0))?;
+    let mut buckets = new_buckets()?;
+    while let Some(record) = read_record(&mut input)? {
+        let bucket = (hash(&record.key, depth) % SPILL_BUCKETS) as usize;
+        write_record(
+            &mut buckets[bucket],
+            record.index,
+            record.count,
+            &record.key,
+            &record.line,
+        )?;
+    }
+    drop(input);
+    let mut sources = vec![];
+    for bucket in buckets {
+        sources.push(first_occurrences(bucket, max_memory, depth)?);
+    }
+    let mut out = BufWriter::new(tempfile::tempfile()?);
+    merge(sources, |record| {
+        write_record(
+            &mut out,
+            record.index,
+            record.count,
+            &record.key,
+            &record.line,
+        )?;
+        Ok(())
+    })?;
+    Ok(records(reopen(out)?))
+}
+
+fn hash(key: &str, seed: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn write_record(
+    out: &mut impl Write,
+    index: u64,
+    count: u64,
+    key: &str,
+    line: &str,
+) -> io::Result<()> {
+    out.write_all(&index.to_le_bytes())?;
+    out.write_all(&count.to_le_bytes())?;
+    for text in [key, line] {
+        out.write_all(&(text.len() as u64).to_le_bytes())?;
+        out.write_all(text.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_record(input: &mut impl BufRead) -> MyResult<Option<Record>> {
+    if input.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let mut number = || -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        input.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    };
+    let index = number()?;
+    let count = number()?;
+    let mut text = || -> MyResult<String> {
+        let mut bytes = [0; 8];
+        input.read_exact(&mut bytes)?;
+        let mut text = vec![0; u64::from_le_bytes(bytes) as usize];
+        input.read_exact(&mut text)?;
+        Ok(String::from_utf8(text)?)
+    };
+    let key = text()?;
+    let line = text()?;
+    Ok(Some(Record {
+        index,
+        count,
+        key,
+        line,
+    }))
+}
+
+fn parse_size(size: &str) -> Result<usize, String> {
+    let (digits, unit) = match size.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&size[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&size[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .ok_or_else(|| format!("invalid size '{}'", size))
+}
+
+/// whether `filename` and `out_file` are the same file on disk
+#[cfg(unix)]
+fn same_file(filename: &str, out_file: &str) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let filename = if filename == "-" {
+        "/dev/stdin"
+    } else {
+        filename
+    };
+    match (fs::metadata(filename), fs::metadata(out_file)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(filename: &str, out_file: &str) -> bool {
+    match (fs::canonicalize(filename), fs::canonicalize(out_file)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// give the temp file the mode of the OUT_FILE it replaces, or a usual 0644
+fn set_permissions(temp: &File, out_file: &str) -> MyResult<()> {
+    match fs::metadata(out_file) {
+        Ok(meta) => temp.set_permissions(meta.permissions())?,
+        #[cfg(unix)]
+        Err(_) => {
+            use std::os::unix::fs::PermissionsExt;
+            temp.set_permissions(fs::Permissions::from_mode(0o644))?
+        }
+        #[cfg(not(unix))]
+        Err(_) => {}
+    }
     Ok(())
 }
 
@@ -83,27 +672,100 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
-fn check(current: &str, prev: &str, ignore_case: &bool, check_chars: &Option<usize>) -> bool {
-    match (*ignore_case, *check_chars) {
-        (true, Some(n)) => {
-            current
-                .trim_end()
-                .to_lowercase()
-                .chars()
-                .take(n)
-                .collect::<String>()
-                != prev
-                    .trim_end()
-                    .to_lowercase()
-                    .chars()
-                    .take(n)
-                    .collect::<String>()
-        }
-        (true, None) => current.trim_end().to_lowercase() != prev.trim_end().to_lowercase(),
-        (false, Some(n)) => {
-            current.trim_end().chars().take(n).collect::<String>()
-                != prev.trim_end().chars().take(n).collect::<String>()
-        }
-        (false, None) => current.trim_end() != prev.trim_end(),
+/// the list grammar of cut: "N", "N-M", "N-" up to the last field and "-N"
+/// from the first, separated by commas
+pub fn parse_pos(range: &str) -> Result<PositionList, String> {
+    let mut result: Vec<Range<usize>> = vec![];
+    for range in range.split(',') {
+        if range.contains('+') || range.contains(char::is_alphabetic) {
+            return Err(format!("illegal list value: \"{range}\""));
+        }
+        let parse = |n: &str| match n.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("illegal list value: \"{}\"", n)),
+        };
+        let temp: Vec<&str> = range.split('-').collect();
+        match temp[..] {
+            [n] => {
+                let end = parse(n)?;
+                result.push(end - 1..end);
+            }
+            ["", ""] => return Err(format!("illegal list value: \"{range}\"")),
+            ["", n] => {
+                let end = parse(n).map_err(|_| format!("illegal list value: \"{range}\""))?;
+                result.push(0..end);
+            }
+            [n, ""] => {
+                let start = parse(n).map_err(|_| format!("illegal list value: \"{range}\""))?;
+                result.push(start - 1..usize::MAX);
+            }
+            [first, last] => {
+                let start = parse(first)?;
+                let end = parse(last)?;
+                if end < start {
+                    return Err(format!(
+                        "First number in range ({start}) must be lower than second number ({end})"
+                    ));
+                }
+                result.push(start - 1..end);
+            }
+            _ => return Err("illegal list values".to_string()),
+        }
+    }
+    Ok(result)
+}
+
+/// read one newline or, with -z, NUL terminated record into `line`
+fn read_line(file: &mut impl BufRead, line: &mut String, zero_terminated: bool) -> MyResult<usize> {
+    if !zero_terminated {
+        return Ok(file.read_line(line)?);
+    }
+    let mut bytes = Vec::new();
+    let n = file.read_until(b'\0', &mut bytes)?;
+    line.push_str(std::str::from_utf8(&bytes)?);
+    Ok(n)
+}
+
+fn check(current: &str, prev: &str, conf: &Config) -> bool {
+    key(current, conf) != key(prev, conf)
+}
+
+/// the part of `line` that takes part in comparisons: keep the --key fields or
+/// skip `-f` fields, then skip `-s` characters, keep at most `-w` characters
+/// and fold case for `-i`
+fn key(line: &str, conf: &Config) -> String {
+    let is_blank = |c: char| c == ' ' || c == '\t';
+    let mut text = if conf.zero_terminated {
+        line.strip_suffix('\0').unwrap_or(line)
+    } else if conf.key.is_some() || conf.skip_fields > 0 || conf.skip_chars > 0 {
+        // blanks at the end are part of the last field, only the newline goes
+        line.strip_suffix('\n').unwrap_or(line)
+    } else {
+        line.trim_end()
+    };
+    let fields;
+    if let Some(pos) = &conf.key {
+        let record: Vec<&str> = text.split(conf.delimiter).collect();
+        fields = pos
+            .iter()
+            .cloned()
+            .flat_map(|range| record.iter().skip(range.start).take(range.len()).copied())
+            .collect::<Vec<_>>()
+            .join(conf.delimiter.encode_utf8(&mut [0; 4]));
+        text = &fields;
+    }
+    for _ in 0..conf.skip_fields {
+        text = text.trim_start_matches(is_blank);
+        text = text.trim_start_matches(|c| !is_blank(c));
+    }
+    let text = text
+        .chars()
+        .skip(conf.skip_chars)
+        .take(conf.check_chars.unwrap_or(usize::MAX))
+        .collect::<String>();
+    if conf.ignore_case {
+        text.to_lowercase()
+    } else {
+        text
     }
 }
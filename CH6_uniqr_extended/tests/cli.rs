@@ -1,8 +1,10 @@
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
 use std::fs;
-use tempfile::NamedTempFile;
+use tempfile::{tempdir, NamedTempFile};
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -80,6 +82,18 @@ const T6: Test = Test {
     out_count: "tests/expected/t6.txt.c.out",
 };
 
+const GROUPS: &str = "tests/inputs/groups.txt";
+const SKIP_FIELDS: &str = "tests/inputs/skip_fields.txt";
+const GLOBAL: &str = "tests/inputs/global.txt";
+const HISTOGRAM: &str = "tests/inputs/histogram.txt";
+const ZERO: &str = "tests/inputs/zero.txt";
+const KEY_CSV: &str = "tests/inputs/key.csv";
+const KEY_TSV: &str = "tests/inputs/key.tsv";
+const TRAILING_TXT: &str = "tests/inputs/trailing.txt";
+const TRAILING_CSV: &str = "tests/inputs/trailing.csv";
+const BLANKS: &str = "tests/inputs/blanks.txt";
+const NO_NEWLINE: &str = "tests/inputs/no_newline.txt";
+
 // --------------------------------------------------
 fn gen_bad_file() -> String {
     loop {
@@ -210,6 +224,17 @@ fn run_stdin_outfile_count(test: &Test) -> TestResult {
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_args(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = fs::read_to_string(expected_file)?;
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn empty() -> TestResult {
@@ -605,3 +630,607 @@ fn t6_outfile_count() -> TestResult {
 fn t6_stdin_outfile_count() -> TestResult {
     run_stdin_outfile_count(&T6)
 }
+
+// --------------------------------------------------
+#[test]
+fn dies_all_repeated_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-D", "-c", GROUPS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+#[test]
+fn dies_group_with_selection() -> TestResult {
+    for flag in ["-c", "-d", "-D", "-u"] {
+        Command::cargo_bin(PRG)?
+            .args(["--group", flag, GROUPS])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+    Ok(())
+}
+
+#[test]
+fn dies_bad_group_method() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--group=middle", GROUPS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'middle'"));
+    Ok(())
+}
+
+#[test]
+fn groups_repeated() -> TestResult {
+    run_args(&["-d", GROUPS], "tests/expected/groups.txt.d.out")
+}
+
+#[test]
+fn groups_repeated_ignore_case() -> TestResult {
+    run_args(&["-d", "-i", GROUPS], "tests/expected/groups.txt.d.i.out")
+}
+
+#[test]
+fn groups_repeated_count() -> TestResult {
+    run_args(&["-d", "-c", GROUPS], "tests/expected/groups.txt.d.c.out")
+}
+
+#[test]
+fn groups_unique() -> TestResult {
+    run_args(&["-u", GROUPS], "tests/expected/groups.txt.u.out")
+}
+
+#[test]
+fn groups_unique_ignore_case() -> TestResult {
+    run_args(&["-u", "-i", GROUPS], "tests/expected/groups.txt.u.i.out")
+}
+
+#[test]
+fn groups_unique_count() -> TestResult {
+    run_args(&["-u", "-c", GROUPS], "tests/expected/groups.txt.u.c.out")
+}
+
+#[test]
+fn groups_repeated_unique() -> TestResult {
+    run_args(&["-d", "-u", GROUPS], "tests/expected/groups.txt.d.u.out")
+}
+
+#[test]
+fn groups_all_repeated_unique() -> TestResult {
+    run_args(&["-D", "-u", GROUPS], "tests/expected/groups.txt.D.u.out")?;
+    run_args(
+        &["--all-repeated=separate", "-u", GROUPS],
+        "tests/expected/groups.txt.D.separate.u.out",
+    )
+}
+
+#[test]
+fn groups_all_repeated() -> TestResult {
+    run_args(&["-D", GROUPS], "tests/expected/groups.txt.D.out")
+}
+
+#[test]
+fn groups_all_repeated_ignore_case() -> TestResult {
+    run_args(&["-D", "-i", GROUPS], "tests/expected/groups.txt.D.i.out")
+}
+
+#[test]
+fn groups_all_repeated_separate() -> TestResult {
+    run_args(
+        &["--all-repeated=separate", GROUPS],
+        "tests/expected/groups.txt.D.separate.out",
+    )
+}
+
+#[test]
+fn groups_all_repeated_separate_ignore_case() -> TestResult {
+    run_args(
+        &["--all-repeated=separate", "-i", GROUPS],
+        "tests/expected/groups.txt.D.separate.i.out",
+    )
+}
+
+#[test]
+fn groups_all_repeated_prepend() -> TestResult {
+    run_args(
+        &["--all-repeated=prepend", GROUPS],
+        "tests/expected/groups.txt.D.prepend.out",
+    )
+}
+
+#[test]
+fn groups_group() -> TestResult {
+    run_args(&["--group", GROUPS], "tests/expected/groups.txt.group.out")
+}
+
+#[test]
+fn groups_group_ignore_case() -> TestResult {
+    run_args(
+        &["--group", "-i", GROUPS],
+        "tests/expected/groups.txt.group.i.out",
+    )
+}
+
+#[test]
+fn groups_group_prepend() -> TestResult {
+    run_args(
+        &["--group=prepend", GROUPS],
+        "tests/expected/groups.txt.group.prepend.out",
+    )
+}
+
+#[test]
+fn groups_group_append() -> TestResult {
+    run_args(
+        &["--group=append", GROUPS],
+        "tests/expected/groups.txt.group.append.out",
+    )
+}
+
+#[test]
+fn groups_group_both() -> TestResult {
+    run_args(
+        &["--group=both", GROUPS],
+        "tests/expected/groups.txt.group.both.out",
+    )
+}
+
+#[test]
+fn skip_fields() -> TestResult {
+    run_args(
+        &["-f", "2", SKIP_FIELDS],
+        "tests/expected/skip_fields.txt.f2.out",
+    )
+}
+
+#[test]
+fn skip_fields_ignore_case() -> TestResult {
+    run_args(
+        &["-f", "2", "-i", SKIP_FIELDS],
+        "tests/expected/skip_fields.txt.f2.i.out",
+    )
+}
+
+#[test]
+fn skip_fields_count() -> TestResult {
+    run_args(
+        &["--skip-fields", "2", "-c", SKIP_FIELDS],
+        "tests/expected/skip_fields.txt.f2.c.out",
+    )
+}
+
+#[test]
+fn skip_fields_skip_chars() -> TestResult {
+    run_args(
+        &["-f", "2", "-s", "5", SKIP_FIELDS],
+        "tests/expected/skip_fields.txt.f2.s5.out",
+    )
+}
+
+#[test]
+fn skip_fields_check_chars_ignore_case() -> TestResult {
+    run_args(
+        &["-f", "2", "-w", "10", "-i", SKIP_FIELDS],
+        "tests/expected/skip_fields.txt.f2.w10.i.out",
+    )
+}
+
+#[test]
+fn skip_chars() -> TestResult {
+    run_args(
+        &["-s", "11", SKIP_FIELDS],
+        "tests/expected/skip_fields.txt.s11.out",
+    )
+}
+
+#[test]
+fn skip_chars_check_chars() -> TestResult {
+    run_args(
+        &["--skip-chars=20", "-w", "4", SKIP_FIELDS],
+        "tests/expected/skip_fields.txt.s20.w4.out",
+    )
+}
+
+// --------------------------------------------------
+fn run_global(args: &[&str], expected_file: &str) -> TestResult {
+    run_args(&[&["--global", GLOBAL], args].concat(), expected_file)?;
+    // a tiny memory cap forces every new key through the spill files
+    run_args(
+        &[&["--global", "--max-memory", "1", GLOBAL], args].concat(),
+        expected_file,
+    )
+}
+
+#[test]
+fn global() -> TestResult {
+    run_global(&[], "tests/expected/global.txt.out")
+}
+
+#[test]
+fn global_count() -> TestResult {
+    run_global(&["-c"], "tests/expected/global.txt.c.out")
+}
+
+#[test]
+fn global_ignore_case() -> TestResult {
+    run_global(&["-i"], "tests/expected/global.txt.i.out")
+}
+
+#[test]
+fn global_check_chars() -> TestResult {
+    run_global(&["-w", "5"], "tests/expected/global.txt.w5.out")
+}
+
+#[test]
+fn global_repeated() -> TestResult {
+    run_global(&["-d"], "tests/expected/global.txt.d.out")
+}
+
+#[test]
+fn global_unique() -> TestResult {
+    run_global(&["-u"], "tests/expected/global.txt.u.out")
+}
+
+#[test]
+fn global_stdin() -> TestResult {
+    let input = fs::read_to_string(GLOBAL)?;
+    let expected = fs::read_to_string("tests/expected/global.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--global", "--max-memory", "1K"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// many keys, so that the buckets spilled past --max-memory are over it too
+#[test]
+fn global_spilled_bucket_over_max_memory() -> TestResult {
+    let lines: Vec<String> = (0..3000)
+        .map(|i| format!("line {:05} of the input\n", (i * 7919) % 1200))
+        .collect();
+    let input = lines.concat();
+    let mut seen = std::collections::HashSet::new();
+    let expected: String = lines
+        .iter()
+        .filter(|line| seen.insert(line.as_str()))
+        .map(String::as_str)
+        .collect();
+    Command::cargo_bin(PRG)?
+        .args(["--global", "--max-memory", "8K"])
+        .write_stdin(input.clone())
+        .assert()
+        .success()
+        .stdout(expected);
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["--global", "-c"])
+        .write_stdin(input.clone())
+        .output()?;
+    Command::cargo_bin(PRG)?
+        .args(["--global", "-c", "--max-memory", "8K"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(output.stdout);
+    Ok(())
+}
+
+#[test]
+fn dies_max_memory_without_global() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--max-memory", "1M", GLOBAL])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--global"));
+    Ok(())
+}
+
+#[test]
+fn dies_bad_max_memory() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--global", "--max-memory", "lots", GLOBAL])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid size 'lots'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn histogram() -> TestResult {
+    run_args(
+        &["--histogram", HISTOGRAM],
+        "tests/expected/histogram.txt.out",
+    )
+}
+
+#[test]
+fn histogram_top() -> TestResult {
+    run_args(
+        &["--histogram", "--top", "3", HISTOGRAM],
+        "tests/expected/histogram.txt.top3.out",
+    )
+}
+
+#[test]
+fn histogram_sort_key() -> TestResult {
+    run_args(
+        &["--histogram", "--sort", "key", HISTOGRAM],
+        "tests/expected/histogram.txt.key.out",
+    )
+}
+
+#[test]
+fn histogram_ignore_case() -> TestResult {
+    run_args(
+        &["--histogram", "-i", HISTOGRAM],
+        "tests/expected/histogram.txt.i.out",
+    )
+}
+
+#[test]
+fn histogram_percent_bar() -> TestResult {
+    run_args(
+        &["--histogram", "--percent", "--bar", HISTOGRAM],
+        "tests/expected/histogram.txt.percent.bar.out",
+    )
+}
+
+#[test]
+fn dies_top_without_histogram() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--top", "3", HISTOGRAM])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--histogram"));
+    Ok(())
+}
+
+#[test]
+fn dies_histogram_repeated() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--histogram", "-d", HISTOGRAM])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+#[test]
+fn dies_histogram_max_memory() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--histogram", "--max-memory", "1M", HISTOGRAM])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn zero_terminated() -> TestResult {
+    run_args(&["-z", ZERO], "tests/expected/zero.txt.out")
+}
+
+#[test]
+fn zero_terminated_count() -> TestResult {
+    run_args(&["-z", "-c", ZERO], "tests/expected/zero.txt.c.out")
+}
+
+#[test]
+fn zero_terminated_ignore_case() -> TestResult {
+    run_args(
+        &["--zero-terminated", "-i", ZERO],
+        "tests/expected/zero.txt.i.out",
+    )
+}
+
+#[test]
+fn zero_terminated_unique() -> TestResult {
+    run_args(&["-z", "-u", ZERO], "tests/expected/zero.txt.u.out")
+}
+
+#[test]
+fn zero_terminated_all_repeated_separate() -> TestResult {
+    run_args(
+        &["-z", "--all-repeated=separate", ZERO],
+        "tests/expected/zero.txt.D.separate.out",
+    )
+}
+
+#[test]
+fn zero_terminated_group_both() -> TestResult {
+    run_args(
+        &["-z", "--group=both", ZERO],
+        "tests/expected/zero.txt.group.both.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn key_field() -> TestResult {
+    run_args(
+        &["--delimiter", ",", "--key", "1", KEY_CSV],
+        "tests/expected/key.csv.k1.out",
+    )
+}
+
+#[test]
+fn key_range_ignore_case() -> TestResult {
+    run_args(
+        &["--delimiter", ",", "--key", "1-2", "-i", KEY_CSV],
+        "tests/expected/key.csv.k1-2.i.out",
+    )
+}
+
+#[test]
+fn key_list_count() -> TestResult {
+    run_args(
+        &["--delimiter", ",", "--key", "2,4", "-c", KEY_CSV],
+        "tests/expected/key.csv.k2,4.c.out",
+    )
+}
+
+#[test]
+fn key_list_global() -> TestResult {
+    run_args(
+        &["--delimiter", ",", "--key", "1,4", "--global", KEY_CSV],
+        "tests/expected/key.csv.k1,4.global.out",
+    )
+}
+
+#[test]
+fn key_default_tab_delimiter() -> TestResult {
+    run_args(
+        &["--key", "2", "-i", KEY_TSV],
+        "tests/expected/key.tsv.k2.i.out",
+    )
+}
+
+#[test]
+fn dies_bad_key() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--key", "0", KEY_TSV])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(r#"illegal list value: "0""#));
+    Ok(())
+}
+
+#[test]
+fn dies_key_skip_fields() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--key", "1", "-f", "1", KEY_TSV])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+#[test]
+fn key_open_ranges() -> TestResult {
+    run_args(
+        &["--delimiter", ",", "--key", "-2", KEY_CSV],
+        "tests/expected/key.csv.k-2.out",
+    )?;
+    run_args(
+        &["--delimiter", ",", "--key", "2-", TRAILING_CSV],
+        "tests/expected/trailing.csv.k2-.out",
+    )
+}
+
+#[test]
+fn key_trailing_blank_field() -> TestResult {
+    run_args(
+        &["--delimiter", ",", "--key", "3", TRAILING_CSV],
+        "tests/expected/trailing.csv.k3.out",
+    )
+}
+
+#[test]
+fn skip_trailing_blanks() -> TestResult {
+    run_args(
+        &["-f", "1", TRAILING_TXT],
+        "tests/expected/trailing.txt.f1.out",
+    )?;
+    run_args(
+        &["-s", "2", TRAILING_TXT],
+        "tests/expected/trailing.txt.s2.out",
+    )
+}
+
+#[test]
+fn whole_lines_trim_trailing_blanks() -> TestResult {
+    run_args(&[BLANKS], "tests/expected/blanks.txt.trimmed.out")?;
+    run_args(&["-c", BLANKS], "tests/expected/blanks.txt.trimmed.c.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_inputs() -> TestResult {
+    run_args(
+        &[GROUPS, HISTOGRAM, "-"],
+        "tests/expected/groups.histogram.out",
+    )
+}
+
+#[test]
+fn multiple_inputs_no_newline() -> TestResult {
+    run_args(
+        &[NO_NEWLINE, GROUPS, "-"],
+        "tests/expected/no_newline.groups.out",
+    )
+}
+
+#[test]
+fn multiple_inputs_outfile() -> TestResult {
+    let dir = tempdir()?;
+    let outpath = dir.path().join("out.txt");
+    Command::cargo_bin(PRG)?
+        .args([GROUPS, HISTOGRAM, outpath.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout("");
+
+    let expected = fs::read_to_string("tests/expected/groups.histogram.out")?;
+    assert_eq!(expected, fs::read_to_string(&outpath)?);
+    Ok(())
+}
+
+#[test]
+fn dies_input_is_output() -> TestResult {
+    let dir = tempdir()?;
+    let path = dir.path().join("in.txt");
+    fs::copy(GROUPS, &path)?;
+    let path = path.to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args([GROUPS, path, path])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("input file is output file"));
+
+    assert_eq!(fs::read_to_string(GROUPS)?, fs::read_to_string(path)?);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn dies_input_is_hard_linked_output() -> TestResult {
+    let dir = tempdir()?;
+    let path = dir.path().join("in.txt");
+    let link = dir.path().join("link.txt");
+    fs::copy(GROUPS, &path)?;
+    fs::hard_link(&path, &link)?;
+    Command::cargo_bin(PRG)?
+        .args([path.to_str().unwrap(), link.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("input file is output file"));
+
+    assert_eq!(fs::read_to_string(GROUPS)?, fs::read_to_string(&link)?);
+    Ok(())
+}
+
+#[test]
+fn keeps_outfile_on_error() -> TestResult {
+    let dir = tempdir()?;
+    let bad = dir.path().join("bad.txt");
+    fs::write(&bad, b"a\na\n\xff\xfe\n")?;
+    let outpath = dir.path().join("out.txt");
+    fs::write(&outpath, "old contents\n")?;
+    Command::cargo_bin(PRG)?
+        .args([GROUPS, bad.to_str().unwrap(), outpath.to_str().unwrap()])
+        .assert()
+        .failure();
+
+    assert_eq!("old contents\n", fs::read_to_string(&outpath)?);
+    // no temp file is left next to the output
+    assert_eq!(2, fs::read_dir(dir.path())?.count());
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! Times the sequential walk against -j on a generated tree, or on the
+//! directory in FINDR_BENCH_DIR, with `cargo bench`.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+const DIRS: usize = 2000;
+const FILES: usize = 50;
+const JOBS: [&str; 3] = ["2", "4", "8"];
+/// runs of each command, of which the fastest counts
+const RUNS: usize = 5;
+
+fn main() {
+    let generated;
+    let tree = match env::var_os("FINDR_BENCH_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            generated = tempfile::tempdir().expect("temporary directory");
+            println!(
+                "Creating {} directories of {} files in \"{}\"",
+                DIRS,
+                FILES,
+                generated.path().display()
+            );
+            make_tree(generated.path());
+            generated.path().to_path_buf()
+        }
+    };
+
+    // warm the page cache so every run reads the same way
+    find(&tree, &[]);
+
+    bench(&tree, &[]);
+    for n in JOBS {
+        bench(&tree, &["-j", n]);
+        bench(&tree, &["-j", n, "--sort"]);
+    }
+}
+
+fn make_tree(root: &Path) {
+    for d in 0..DIRS {
+        let dir = root.join(format!("{}/{}/{}", d % 10, d % 100, d));
+        fs::create_dir_all(&dir).expect("directory");
+        for f in 1..=FILES {
+            fs::write(dir.join(format!("file{}.txt", f)), "").expect("file");
+        }
+    }
+}
+
+fn bench(tree: &Path, args: &[&str]) {
+    let best = (0..RUNS).map(|_| find(tree, args)).min().unwrap();
+    let name = if args.is_empty() {
+        "sequential".to_string()
+    } else {
+        args.join(" ")
+    };
+    println!("{:<16} {:.3}s", name, best.as_secs_f64());
+}
+
+fn find(tree: &Path, args: &[&str]) -> Duration {
+    let start = Instant::now();
+    let status = Command::new(env!("CARGO_BIN_EXE_findr"))
+        .args(args)
+        .arg(tree)
+        .args(["-name", "*7.txt"])
+        .stdout(Stdio::null())
+        .status()
+        .expect("findr");
+    assert!(status.success());
+    start.elapsed()
+}
@@ -39,3 +39,12 @@ find "$IN_DIR/a" "$IN_DIR/d" -name \*.txt > "$OUT_DIR/name_txt_path_a_d.txt"
 find "$IN_DIR" -name a* > "$OUT_DIR/name_a.txt"
 find "$IN_DIR" -type f -name a* > "$OUT_DIR/type_f_name_a.txt"
 find "$IN_DIR" -type d -name a* > "$OUT_DIR/type_d_name_a.txt"
+
+find "$IN_DIR" \( -name \*a\* -type f \) -o ! -name \*.\* > "$OUT_DIR/expr_name_type_or_not_name.txt"
+find "$IN_DIR" -type d -not -name a -not -name b -not -name c > "$OUT_DIR/expr_type_d_not_name.txt"
+find "$IN_DIR" -type f \( -name \*csv\* -o -name \*mp3\* \) > "$OUT_DIR/expr_type_flag_and_or.txt"
+find "$IN_DIR" -type f,l -name \*.csv > "$OUT_DIR/expr_type_list.txt"
+find "$IN_DIR" -printf '%d %y %f\n' > "$OUT_DIR/printf_depth_type_name.txt"
+find "$IN_DIR" -iname \*.CSV > "$OUT_DIR/iname_csv.txt"
+find "$IN_DIR" -path \*/a/\* > "$OUT_DIR/path_glob_a.txt"
+find "$IN_DIR" -regex '.*/[a-c]' > "$OUT_DIR/regex_single_letter.txt"
@@ -1,7 +1,33 @@
 use crate::EntryType::*;
-use clap::{Parser, ValueEnum};
+use chrono::{DateTime, Local};
+use clap::{CommandFactory, Parser, ValueEnum};
+use crossbeam_deque::{Injector, Stealer, Worker};
+use ignore::{
+    gitignore::{gitconfig_excludes_path, Gitignore, GitignoreBuilder},
+    Match,
+};
 use regex::Regex;
-use std::error::Error;
+use serde::Serialize;
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    cmp,
+    collections::{HashMap, HashSet},
+    error::Error,
+    ffi::{OsStr, OsString},
+    fs::{self, FileType, Metadata},
+    io::{self, BufRead, BufReader, Write},
+    iter::Peekable,
+    path::{Path, PathBuf},
+    process::Command,
+    slice::Iter,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::SystemTime,
+};
 use walkdir::{DirEntry, WalkDir};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
@@ -11,13 +37,85 @@ type MyResult<T> = Result<T, Box<dyn Error>>;
     version = "0.2.0",
     author = "huzwares <huzwares@skiff.com>",
     name = "findr",
-    about = "Rust find"
+    about = "Rust find",
+    override_usage = "findr [-H] [-L] [OPTIONS] [PATH]... [EXPRESSION]",
+    after_help = "\
+Expression:
+  Tests and operators after the paths, evaluated left to right per entry:
+    -name GLOB      file name matches the shell pattern GLOB (*, ?, [...])
+    -iname GLOB     like -name, ignoring case
+    -path GLOB      whole path matches GLOB, where * and ? also match /
+    -ipath GLOB     like -path, ignoring case
+    -regex REGEX    whole path matches REGEX, not just a part of it
+    -iregex REGEX   like -regex, ignoring case
+    -type TYPE      entry type is d, f, l, p, s, b or c (or a comma separated list)
+    -size [+-]N[ckMG]
+                    size in 512-byte blocks, bytes, KiB, MiB or GiB, rounded up
+    -mtime [+-]N, -atime [+-]N, -ctime [+-]N
+                    modified, accessed or changed N days ago
+    -mmin [+-]N, -amin [+-]N, -cmin [+-]N
+                    modified, accessed or changed N minutes ago
+    -newer FILE     modified more recently than FILE
+    -empty          empty file or directory
+    -perm MODE      permission bits are exactly MODE, octal or symbolic (u+w,g=rx)
+    -perm -MODE     all of the bits of MODE are set
+    -perm /MODE     any of the bits of MODE is set
+    -user NAME, -group NAME
+                    owned by the user or group NAME (or number)
+    -uid [+-]N, -gid [+-]N
+                    owned by the user or group with id N
+    -nouser, -nogroup
+                    the owner's id has no user or group
+    -inum [+-]N     inode number is N
+    -links [+-]N    has N hard links
+    -samefile FILE  the same file as FILE, through a hard link or itself
+    -contains REGEX a line of the regular file matches REGEX
+    -contains-fixed STR
+                    a line of the regular file has STR in it; both are tried
+                    after the tests they are ANDed with, skip files with a NUL
+                    in their first 8 KiB unless --binary and stop at a match
+    -prune          always true; do not descend into the directory
+    -true, -false   always true, always false
+    ( EXPR )        grouping
+    ! EXPR, -not EXPR
+    EXPR -a EXPR, EXPR -and EXPR, EXPR EXPR
+    EXPR -o EXPR, EXPR -or EXPR
+  Actions, which are true when they succeed:
+    -print          print the path; the default when there is no other action
+    -print0         print the path followed by a NUL instead of a newline
+    -printf FORMAT  print FORMAT, with \n, \t, \0 and \\ escapes and directives
+                    %p path, %f name, %h directory, %s size, %m octal mode,
+                    %M symbolic mode, %u user, %g group, %i inode, %n links,
+                    %d depth, %y type, %l link target, %t modification time,
+                    %Tk its field k (Y m d H M S T + @ ...), %a %Ak access time,
+                    %c %Ck change time, %% a percent sign; %-10p pads to 10
+    -ls             print the entry like ls -dils
+    -exec CMD ;     run CMD, with each {} in it replaced by the path
+    -exec CMD {} +  run CMD on as many paths at once as fit on a command line
+    -execdir CMD ;, -execdir CMD {} +
+                    like -exec, from the directory containing the entry
+    -ok CMD ;, -okdir CMD ;
+                    like -exec and -execdir, asking first
+    -delete         delete the entry; implies -depth
+  Options, which are always true and apply to the whole walk:
+    -depth          visit a directory's contents before the directory
+    -xdev, -mount   do not descend into other file systems
+  --name, a REGEX found anywhere in the file name, and --type are ANDed
+  with the expression."
 )]
 pub struct Config {
     /// Serach paths
     #[arg(value_name = "PATH", default_value = ".")]
     path: Vec<String>,
 
+    /// Follow symbolic links
+    #[arg(short = 'L')]
+    follow_links: bool,
+
+    /// Follow symbolic links given as paths
+    #[arg(short = 'H')]
+    follow_root_links: bool,
+
     /// Name
     #[arg(short, long, value_name = "NAME", num_args = 1..)]
     name: Vec<Regex>,
@@ -26,10 +124,16 @@ pub struct Config {
     #[arg(value_enum, short = 't', long = "type", value_name = "TYPE", num_args = 1..)]
     entry_type: Vec<EntryType>,
 
-    /// Show counter
+    /// Show how many entries of each type matched, and their bytes, for
+    /// each path and in total
     #[arg(short = 'c', long, value_name = "COUNT")]
     count: bool,
 
+    /// Print the --count report as text or JSON; with JSON the report is
+    /// all that is printed unless the expression has -print or another action
+    #[arg(value_enum, long, value_name = "FORMAT", default_value_t = CountFormat::Text, requires = "count")]
+    count_format: CountFormat,
+
     /// Set max depth
     #[arg(long, value_name = "MAX_DEPTH")]
     max_depth: Option<usize>,
@@ -37,9 +141,54 @@ pub struct Config {
     /// Set min depth
     #[arg(long, value_name = "MIN_DEPTH")]
     min_depth: Option<usize>,
+
+    /// Read directories with N threads, printing in no particular order
+    #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: Option<u16>,
+
+    /// Visit the entries of each directory in name, size or modification
+    /// time order, name when not given; path also puts the paths given in
+    /// order
+    #[arg(
+        value_enum,
+        long,
+        value_name = "KEY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "name"
+    )]
+    sort: Option<SortKey>,
+
+    /// Sort the other way round
+    #[arg(long, requires = "sort")]
+    reverse: bool,
+
+    /// Print what -print would as an indented tree, after each path's walk
+    #[arg(long)]
+    tree: bool,
+
+    /// Let -contains and -contains-fixed search binary files too
+    #[arg(long)]
+    binary: bool,
+
+    /// Skip what .gitignore, .ignore, .git/info/exclude and the global git
+    /// excludes file ignore, and .git directories
+    #[arg(long)]
+    gitignore: bool,
+
+    /// Include entries whose name starts with a dot (the default)
+    #[arg(long, overrides_with = "no_hidden")]
+    hidden: bool,
+
+    /// Skip entries whose name starts with a dot, and what is below them
+    #[arg(long, overrides_with = "hidden")]
+    no_hidden: bool,
+
+    #[arg(skip)]
+    expr: Expr,
 }
 
-#[derive(Debug, Clone, ValueEnum, PartialEq)]
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
 enum EntryType {
     #[value(alias = "d")]
     Dir,
@@ -47,96 +196,2881 @@ enum EntryType {
     File,
     #[value(alias = "l")]
     Link,
+    #[value(alias = "p")]
+    Fifo,
+    #[value(alias = "s")]
+    Socket,
+    #[value(alias = "b")]
+    Block,
+    #[value(alias = "c")]
+    Char,
 }
 
-pub fn get_args() -> MyResult<Config> {
-    let conf = Config::parse();
-    Ok(conf)
+impl EntryType {
+    /// the letter find and `type_char` use for it
+    fn letter(self) -> char {
+        match self {
+            Dir => 'd',
+            File => 'f',
+            Link => 'l',
+            Fifo => 'p',
+            Socket => 's',
+            Block => 'b',
+            Char => 'c',
+        }
+    }
 }
 
-pub fn run(conf: Config) -> MyResult<()> {
-    let (mut link_count, mut dir_count, mut file_count) = (0, 0, 0);
-    let type_filter = |e: &DirEntry| {
-        conf.entry_type.is_empty()
-            || conf.entry_type.iter().any(|t| match t {
-                Link => e.file_type().is_symlink(),
-                Dir => e.file_type().is_dir(),
-                File => e.file_type().is_file(),
-            })
-    };
-    let name_filter = |e: &DirEntry| {
-        conf.name.is_empty()
-            || conf
-                .name
-                .iter()
-                .any(|r| r.is_match(&e.file_name().to_string_lossy()))
-    };
-    for path in &conf.path {
-        let entries = WalkDir::new(path)
-            .min_depth(conf.min_depth.unwrap_or(0))
-            .max_depth(conf.max_depth.unwrap_or(usize::MAX))
-            .into_iter()
-            .filter_map(|res| match res {
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+enum SortKey {
+    Name,
+    Path,
+    Size,
+    Mtime,
+}
+
+/// the order --sort and --reverse ask for
+#[derive(Debug, Clone, Copy)]
+struct Sort {
+    key: SortKey,
+    reverse: bool,
+}
+
+impl Sort {
+    /// Orders entries of the same directory, by name when the key is equal.
+    fn compare<T: Sortable>(&self, a: &T, b: &T) -> cmp::Ordering {
+        let by_name = a.name().cmp(b.name());
+        let order = match self.key {
+            SortKey::Name | SortKey::Path => by_name,
+            SortKey::Size => {
+                let size = |e: &T| e.meta().map(|m| m.len());
+                size(a).cmp(&size(b)).then(by_name)
+            }
+            SortKey::Mtime => {
+                let mtime = |e: &T| e.meta().and_then(|m| m.modified().ok());
+                mtime(a).cmp(&mtime(b)).then(by_name)
+            }
+        };
+        self.apply(order)
+    }
+
+    fn apply(&self, order: cmp::Ordering) -> cmp::Ordering {
+        if self.reverse {
+            order.reverse()
+        } else {
+            order
+        }
+    }
+}
+
+/// what `Sort` needs of walkdir's entries and ours
+trait Sortable {
+    fn name(&self) -> &OsStr;
+    fn meta(&self) -> Option<Metadata>;
+}
+
+impl Sortable for DirEntry {
+    fn name(&self) -> &OsStr {
+        self.file_name()
+    }
+
+    fn meta(&self) -> Option<Metadata> {
+        self.metadata().ok()
+    }
+}
+
+impl Sortable for Entry {
+    fn name(&self) -> &OsStr {
+        self.file_name()
+    }
+
+    fn meta(&self) -> Option<Metadata> {
+        self.metadata().ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+enum CountFormat {
+    Text,
+    Json,
+}
+
+/// what --count reports for one path, or for all of them
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+struct Counts {
+    directories: u64,
+    files: u64,
+    links: u64,
+    fifos: u64,
+    sockets: u64,
+    block_devices: u64,
+    char_devices: u64,
+    /// the sizes of the regular files
+    bytes: u64,
+    /// the entries at each depth, from the starting point's 0 on
+    depths: Vec<u64>,
+}
+
+impl Counts {
+    fn add(&mut self, visit: &Visit) {
+        let file_type = visit.entry.file_type();
+        let count = match type_char(file_type) {
+            'd' => &mut self.directories,
+            'l' => &mut self.links,
+            'p' => &mut self.fifos,
+            's' => &mut self.sockets,
+            'b' => &mut self.block_devices,
+            'c' => &mut self.char_devices,
+            _ => &mut self.files,
+        };
+        *count += 1;
+        let depth = visit.entry.depth();
+        if self.depths.len() <= depth {
+            self.depths.resize(depth + 1, 0);
+        }
+        self.depths[depth] += 1;
+        if file_type.is_file() {
+            self.bytes += visit.metadata().map_or(0, |m| m.len());
+        }
+    }
+
+    fn total<'a>(all: impl Iterator<Item = &'a Counts>) -> Counts {
+        all.fold(Counts::default(), |mut total, counts| {
+            total.directories += counts.directories;
+            total.files += counts.files;
+            total.links += counts.links;
+            total.fifos += counts.fifos;
+            total.sockets += counts.sockets;
+            total.block_devices += counts.block_devices;
+            total.char_devices += counts.char_devices;
+            total.bytes += counts.bytes;
+            if total.depths.len() < counts.depths.len() {
+                total.depths.resize(counts.depths.len(), 0);
+            }
+            for (total, count) in total.depths.iter_mut().zip(&counts.depths) {
+                *total += count;
+            }
+            total
+        })
+    }
+
+    /// Prints a line for each type found, always the bytes, and then a row
+    /// for each depth.
+    fn print(&self, out: &mut impl Write, title: &str) -> io::Result<()> {
+        writeln!(out, "--------- {} ---------", title)?;
+        for (count, what) in [
+            (self.directories, "directories"),
+            (self.files, "files"),
+            (self.links, "links"),
+            (self.fifos, "fifos"),
+            (self.sockets, "sockets"),
+            (self.block_devices, "block devices"),
+            (self.char_devices, "character devices"),
+        ] {
+            if count > 0 {
+                writeln!(out, "{count} {what}")?;
+            }
+        }
+        writeln!(out, "{} bytes", self.bytes)?;
+        if !self.depths.is_empty() {
+            writeln!(out, "depth entries")?;
+            for (depth, count) in self.depths.iter().enumerate() {
+                writeln!(out, "{depth:>5} {count}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the --count report, printed after everything else
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    paths: Vec<PathCounts<'a>>,
+    total: Counts,
+}
+
+#[derive(Debug, Serialize)]
+struct PathCounts<'a> {
+    path: &'a str,
+    #[serde(flatten)]
+    counts: Counts,
+}
+
+impl Report<'_> {
+    fn print(&self, format: CountFormat) -> MyResult<()> {
+        let mut out = io::stdout().lock();
+        match format {
+            CountFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(self)?)?,
+            CountFormat::Text => {
+                for path in &self.paths {
+                    path.counts.print(&mut out, path.path)?;
+                }
+                if self.paths.len() > 1 {
+                    self.total.print(&mut out, "total")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// find-style expression over an `Entry`
+#[derive(Debug, Default)]
+enum Expr {
+    #[default]
+    True,
+    False,
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Name(Regex),
+    Path(Regex),
+    Type(EntryType),
+    /// size in units of the given number of bytes, rounded up
+    Size(Cmp, i64),
+    /// age in units of the given number of seconds, rounded down
+    Time(TimeField, Cmp, i64, SystemTime),
+    Newer(SystemTime),
+    Empty,
+    Perm(PermMatch, u32),
+    Uid(Cmp),
+    Gid(Cmp),
+    NoUser,
+    NoGroup,
+    Inum(Cmp),
+    Links(Cmp),
+    /// device and inode numbers
+    SameFile(u64, u64),
+    Contains(Contains),
+    Print,
+    Print0,
+    Printf(Vec<Piece>),
+    Ls,
+    Exec(Exec),
+    /// set when a deletion failed
+    Delete(Cell<bool>),
+    Prune,
+    Depth,
+    Xdev,
+    /// -print under --tree: the paths printed so far
+    Tree(RefCell<Vec<PathBuf>>),
+}
+
+/// a run of a -printf format
+#[derive(Debug, PartialEq)]
+enum Piece {
+    Text(Vec<u8>),
+    Directive {
+        /// the letter after `%`, and after `T`, `A` or `C` the time field's letter
+        letter: char,
+        field: Option<char>,
+        width: usize,
+        left: bool,
+    },
+}
+
+/// `-exec`, `-execdir`, `-ok` and `-okdir`
+#[derive(Debug, Default)]
+struct Exec {
+    /// the command and its arguments, without the `{} +` of the batched form
+    command: Vec<String>,
+    /// run from the directory containing the entry
+    in_dir: bool,
+    /// ask on stderr before each run
+    confirm: bool,
+    /// the batched form's paths waiting for the next run
+    batch: Option<RefCell<Batch>>,
+    /// set when a batched run failed
+    failed: Cell<bool>,
+    /// bytes of the batched form's command line left for paths
+    room: usize,
+}
+
+#[derive(Debug, Default)]
+struct Batch {
+    dir: Option<PathBuf>,
+    paths: Vec<OsString>,
+    size: usize,
+}
+
+/// the room assumed for a command line and the environment when the system
+/// will not tell, as on Linux with its default stack size
+const ARG_MAX: usize = 128 * 1024;
+
+/// the system's limit on a command line and the environment
+fn arg_max() -> usize {
+    #[cfg(unix)]
+    {
+        // SAFETY: sysconf only reads a system setting
+        let max = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+        if max > 0 {
+            return max as usize;
+        }
+    }
+    ARG_MAX
+}
+
+/// `+N`, `-N` or `N` argument of the numeric tests
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cmp {
+    More(i64),
+    Less(i64),
+    Exactly(i64),
+}
+
+/// how -perm compares the permission bits
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PermMatch {
+    Exactly,
+    All,
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TimeField {
+    Accessed,
+    Changed,
+    Modified,
+}
+
+/// a path found by either walker
+struct Entry {
+    path: PathBuf,
+    /// the type of the link's target when the link is followed
+    file_type: FileType,
+    depth: usize,
+    /// the path itself is a symbolic link
+    link: bool,
+    follow: bool,
+}
+
+impl Entry {
+    /// a starting point, following it when it is a link and `follow` is set
+    fn root(path: &str, follow: bool) -> Result<Entry, WalkError> {
+        let path = PathBuf::from(path);
+        let file_type = fs::symlink_metadata(&path)
+            .map_err(|e| WalkError::io(&path, e))?
+            .file_type();
+        Ok(Entry::new(path, file_type, 0, follow))
+    }
+
+    fn new(path: PathBuf, file_type: FileType, depth: usize, follow: bool) -> Entry {
+        let link = file_type.is_symlink();
+        let target = (link && follow).then(|| fs::metadata(&path).ok()).flatten();
+        Entry {
+            file_type: target.as_ref().map_or(file_type, Metadata::file_type),
+            follow: target.is_some(),
+            path,
+            depth,
+            link,
+        }
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn file_name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+
+    fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn path_is_symlink(&self) -> bool {
+        self.link
+    }
+
+    fn metadata(&self) -> io::Result<Metadata> {
+        if self.follow {
+            fs::metadata(&self.path)
+        } else {
+            fs::symlink_metadata(&self.path)
+        }
+    }
+}
+
+impl From<DirEntry> for Entry {
+    fn from(entry: DirEntry) -> Self {
+        Entry {
+            file_type: entry.file_type(),
+            depth: entry.depth(),
+            link: entry.path_is_symlink(),
+            follow: entry.path_is_symlink() && !entry.file_type().is_symlink(),
+            path: entry.into_path(),
+        }
+    }
+}
+
+/// a walk error, ready to print, and whether it is a file system loop
+struct WalkError {
+    message: String,
+    is_loop: bool,
+}
+
+impl WalkError {
+    fn io(path: &Path, e: io::Error) -> WalkError {
+        WalkError {
+            message: format!("IO error for operation on {}: {}", path.display(), e),
+            is_loop: false,
+        }
+    }
+}
+
+impl From<walkdir::Error> for WalkError {
+    fn from(e: walkdir::Error) -> Self {
+        WalkError {
+            is_loop: e.loop_ancestor().is_some(),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// an entry under evaluation; its metadata is only read once a test needs it
+struct Visit<'a> {
+    entry: &'a Entry,
+    metadata: OnceCell<Option<Metadata>>,
+    /// set by -prune
+    prune: Cell<bool>,
+    /// set when an action could not write to stdout
+    write_error: Cell<Option<io::Error>>,
+}
+
+impl<'a> Visit<'a> {
+    fn new(entry: &'a Entry) -> Self {
+        Visit {
+            entry,
+            metadata: OnceCell::new(),
+            prune: Cell::new(false),
+            write_error: Cell::new(None),
+        }
+    }
+
+    /// writes `bytes` to stdout, keeping the error for the walk to stop on
+    fn write_out(&self, bytes: &[u8]) -> bool {
+        write_out(bytes).map_err(|e| self.write_error.set(Some(e))).is_ok()
+    }
+
+    fn stat(&self) -> Option<Stat> {
+        self.metadata().map(Stat::new)
+    }
+
+    fn metadata(&self) -> Option<&Metadata> {
+        self.metadata
+            .get_or_init(|| match self.entry.metadata() {
+                Ok(metadata) => Some(metadata),
                 Err(e) => {
-                    eprintln!("{}", e);
+                    eprintln!("{}: {}", self.entry.path().display(), e);
                     None
                 }
-                Ok(entry) => Some(entry),
             })
-            .filter(type_filter)
-            .filter(name_filter)
-            .map(|entry| {
-                match entry.file_type() {
-                    e if e.is_dir() => {
-                        dir_count += 1;
-                    }
-                    e if e.is_file() => {
-                        file_count += 1;
-                    }
-                    e if e.is_symlink() => {
-                        link_count += 1;
-                    }
-                    _ => (),
+            .as_ref()
+    }
+}
+
+impl Cmp {
+    /// split `[+-]N` off the front of `arg`, returning the rest
+    fn parse(arg: &str) -> Option<(Cmp, &str)> {
+        let (cmp, digits): (fn(i64) -> Cmp, &str) = match arg.as_bytes().first() {
+            Some(b'+') => (Cmp::More, &arg[1..]),
+            Some(b'-') => (Cmp::Less, &arg[1..]),
+            _ => (Cmp::Exactly, arg),
+        };
+        let end = digits
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(digits.len());
+        let n = digits[..end].parse().ok()?;
+        Some((cmp(n), &digits[end..]))
+    }
+
+    fn matches(self, n: i64) -> bool {
+        match self {
+            Cmp::More(m) => n > m,
+            Cmp::Less(m) => n < m,
+            Cmp::Exactly(m) => n == m,
+        }
+    }
+}
+
+impl TimeField {
+    fn get(self, metadata: &Metadata) -> Option<SystemTime> {
+        match self {
+            TimeField::Accessed => metadata.accessed().ok(),
+            TimeField::Modified => metadata.modified().ok(),
+            #[cfg(unix)]
+            TimeField::Changed => {
+                use std::os::unix::fs::MetadataExt;
+                let since = std::time::Duration::new(
+                    metadata.ctime().try_into().ok()?,
+                    metadata.ctime_nsec().try_into().ok()?,
+                );
+                Some(SystemTime::UNIX_EPOCH + since)
+            }
+            #[cfg(not(unix))]
+            TimeField::Changed => metadata.created().ok(),
+        }
+    }
+}
+
+/// whole seconds from `then` to `now`, negative for times in the future
+fn seconds_between(then: SystemTime, now: SystemTime) -> i64 {
+    match now.duration_since(then) {
+        Ok(age) => age.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64) - 1,
+    }
+}
+
+/// operators and tests that start the expression part of the command line
+const EXPR_WORDS: &[&str] = &[
+    "(",
+    ")",
+    "!",
+    "-not",
+    "-a",
+    "-and",
+    "-o",
+    "-or",
+    "-true",
+    "-false",
+    "-name",
+    "-iname",
+    "-path",
+    "-ipath",
+    "-regex",
+    "-iregex",
+    "-type",
+    "-size",
+    "-mtime",
+    "-atime",
+    "-ctime",
+    "-mmin",
+    "-amin",
+    "-cmin",
+    "-newer",
+    "-empty",
+    "-perm",
+    "-user",
+    "-group",
+    "-uid",
+    "-gid",
+    "-nouser",
+    "-nogroup",
+    "-inum",
+    "-links",
+    "-samefile",
+    "-contains",
+    "-contains-fixed",
+    "-print",
+    "-print0",
+    "-printf",
+    "-ls",
+    "-exec",
+    "-execdir",
+    "-ok",
+    "-okdir",
+    "-delete",
+    "-prune",
+    "-depth",
+    "-xdev",
+    "-mount",
+];
+
+pub fn get_args() -> MyResult<Config> {
+    let args = std::env::args_os()
+        .map(|arg| {
+            arg.into_string()
+                .map_err(|arg| format!("invalid UTF-8 argument: {:?}", arg))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let command = Config::command();
+    // like find, everything from the first test or operator on is the
+    // expression, though not the values of options before it
+    let mut start = 1;
+    while let Some(arg) = args.get(start) {
+        if EXPR_WORDS.contains(&arg.as_str()) {
+            break;
+        }
+        start += 1 + option_values(&command, &args[start..]).unwrap_or(0);
+    }
+    let start = start.min(args.len());
+    // options can follow the expression too, wherever a test could
+    let (mut expr, options) = Expr::parse(&args[start..])?;
+    let mut conf = Config::parse_from(args[..start].iter().chain(&options));
+    if conf.binary {
+        expr.set_binary();
+    }
+    if let Some(types) = any_of(conf.entry_type.iter().map(|t| Expr::Type(*t))) {
+        expr = Expr::And(Box::new(types), Box::new(expr));
+    }
+    if let Some(names) = any_of(conf.name.iter().map(|r| Expr::Name(r.clone()))) {
+        expr = Expr::And(Box::new(names), Box::new(expr));
+    }
+    // so the JSON report can be read as is, it goes without the paths
+    if !expr.any(&Expr::is_action) && conf.count_format != CountFormat::Json {
+        expr = Expr::And(Box::new(expr), Box::new(Expr::Print));
+    }
+    if conf.tree {
+        expr = expr.into_tree();
+    }
+    conf.expr = expr;
+    Ok(conf)
+}
+
+/// How many of the arguments after `args[0]` clap takes as its values, when
+/// it is an option: one for an option with a value, unless attached, and for
+/// those with several, more up to the next option or expression word.
+fn option_values(command: &clap::Command, args: &[String]) -> Option<usize> {
+    let arg = args.first()?;
+    if EXPR_WORDS.contains(&arg.as_str()) {
+        return None;
+    }
+    let option = if let Some(long) = arg.strip_prefix("--").filter(|l| !l.is_empty()) {
+        let (name, attached) = match long.split_once('=') {
+            Some((name, _)) => (name, true),
+            None => (long, false),
+        };
+        // unknown long options are left for clap to complain about
+        let Some(option) = command.get_arguments().find(|a| a.get_long() == Some(name)) else {
+            return Some(0);
+        };
+        if attached {
+            return Some(0);
+        }
+        option
+    } else {
+        let shorts = arg.strip_prefix('-').filter(|s| !s.is_empty())?;
+        let mut chars = shorts.chars();
+        loop {
+            let c = chars.next()?;
+            let option = command.get_arguments().find(|a| a.get_short() == Some(c))?;
+            if option.get_action().takes_values() {
+                if !chars.as_str().is_empty() {
+                    return Some(0);
                 }
-                entry.path().display().to_string()
-            })
-            .collect::<Vec<_>>();
-        println!("{}", entries.join("\n"));
-        if conf.count {
-            println!("--------- counter ---------");
-            if dir_count > 0 {
-                println!("{dir_count} directories");
+                break option;
+            }
+            if chars.as_str().is_empty() {
+                return Some(0);
+            }
+        }
+    };
+    // an optional value is only ever attached
+    if !option.get_action().takes_values() || option.is_require_equals_set() {
+        return Some(0);
+    }
+    let several = option.get_num_args().is_some_and(|n| n.max_values() > 1);
+    let rest = &args[1..];
+    let more = rest
+        .iter()
+        .skip(1)
+        .take_while(|a| several && !a.starts_with('-') && !EXPR_WORDS.contains(&a.as_str()))
+        .count();
+    Some(rest.len().min(1) + more)
+}
+
+/// the `-o` chain of `exprs`, `None` when there are none
+fn any_of(exprs: impl Iterator<Item = Expr>) -> Option<Expr> {
+    exprs.reduce(|a, b| Expr::Or(Box::new(a), Box::new(b)))
+}
+
+impl Expr {
+    /// The options found where a test could be come back with the
+    /// expression, values included.
+    fn parse(args: &[String]) -> MyResult<(Expr, Vec<String>)> {
+        let command = Config::command();
+        let mut parser = ExprParser {
+            tokens: args.iter().peekable(),
+            command: &command,
+            options: vec![],
+        };
+        parser.take_options();
+        if parser.peek().is_none() {
+            return Ok((Expr::True, parser.options));
+        }
+        let expr = parser.or()?;
+        match parser.next() {
+            None => Ok((expr, parser.options)),
+            Some(_) => Err(From::from("invalid expression; you have too many ')'")),
+        }
+    }
+
+    fn eval(&self, visit: &Visit) -> bool {
+        let entry = visit.entry;
+        match self {
+            Expr::True => true,
+            Expr::False => false,
+            Expr::Not(expr) => !expr.eval(visit),
+            Expr::And(left, right) => left.eval(visit) && right.eval(visit),
+            Expr::Or(left, right) => left.eval(visit) || right.eval(visit),
+            Expr::Name(regex) => regex.is_match(&entry.file_name().to_string_lossy()),
+            Expr::Path(regex) => regex.is_match(&entry.path().to_string_lossy()),
+            Expr::Type(t) => type_char(entry.file_type()) == t.letter(),
+            Expr::Size(cmp, unit) => visit
+                .metadata()
+                .is_some_and(|m| cmp.matches((m.len() as i64 + unit - 1) / unit)),
+            Expr::Time(field, cmp, unit, now) => visit
+                .metadata()
+                .and_then(|m| field.get(m))
+                .is_some_and(|t| cmp.matches(seconds_between(t, *now).div_euclid(*unit))),
+            Expr::Newer(reference) => visit
+                .metadata()
+                .and_then(|m| m.modified().ok())
+                .is_some_and(|t| t > *reference),
+            Expr::Empty => {
+                if entry.file_type().is_dir() {
+                    fs::read_dir(entry.path()).is_ok_and(|mut dir| dir.next().is_none())
+                } else {
+                    entry.file_type().is_file() && visit.metadata().is_some_and(|m| m.len() == 0)
+                }
+            }
+            Expr::Perm(how, bits) => visit.stat().is_some_and(|stat| {
+                let mode = stat.mode & 0o7777;
+                match how {
+                    PermMatch::Exactly => mode == *bits,
+                    PermMatch::All => mode & bits == *bits,
+                    PermMatch::Any => *bits == 0 || mode & bits != 0,
+                }
+            }),
+            Expr::Uid(cmp) => visit.stat().is_some_and(|s| cmp.matches(s.uid.into())),
+            Expr::Gid(cmp) => visit.stat().is_some_and(|s| cmp.matches(s.gid.into())),
+            Expr::NoUser => visit.stat().is_some_and(|s| !has_user(s.uid)),
+            Expr::NoGroup => visit.stat().is_some_and(|s| !has_group(s.gid)),
+            Expr::Inum(cmp) => visit.stat().is_some_and(|s| cmp.matches(s.ino as i64)),
+            Expr::Links(cmp) => visit.stat().is_some_and(|s| cmp.matches(s.nlink as i64)),
+            Expr::SameFile(dev, ino) => {
+                visit.stat().is_some_and(|s| (s.dev, s.ino) == (*dev, *ino))
+            }
+            Expr::Contains(contains) => contains.eval(entry),
+            Expr::Tree(paths) => {
+                paths.borrow_mut().push(entry.path().to_path_buf());
+                true
+            }
+            Expr::Print => {
+                visit.write_out(&[&os_bytes(entry.path().as_os_str())[..], b"\n"].concat())
+            }
+            Expr::Print0 => {
+                visit.write_out(&[&os_bytes(entry.path().as_os_str())[..], b"\0"].concat())
             }
-            if file_count > 0 {
-                println!("{file_count} files");
+            Expr::Printf(pieces) => visit.write_out(&printf(pieces, visit)),
+            Expr::Ls => visit.write_out(&ls(visit)),
+            Expr::Exec(exec) => exec.eval(entry),
+            Expr::Prune => {
+                visit.prune.set(true);
+                true
             }
-            if link_count > 0 {
-                println!("{link_count} links");
+            Expr::Depth | Expr::Xdev => true,
+            Expr::Delete(failed) => {
+                // like find, quietly leave the starting point "."
+                if entry.path() == Path::new(".") {
+                    return true;
+                }
+                let res = if entry.file_type().is_dir() {
+                    fs::remove_dir(entry.path())
+                } else {
+                    fs::remove_file(entry.path())
+                };
+                res.map_err(|e| {
+                    eprintln!("{}: {}", entry.path().display(), e);
+                    failed.set(true);
+                })
+                .is_ok()
             }
         }
-        // for entry in WalkDir::new(path) {
-        //     match entry {
-        //         Err(e) => eprintln!("{}", e),
-        //         Ok(entry) => {
-        //             let file_type = if entry.file_type().is_dir() {
-        //                 Dir
-        //             } else if entry.file_type().is_file() {
-        //                 File
-        //             } else {
-        //                 Link
-        //             };
-        //             if (conf.entry_type.is_empty() || conf.entry_type.contains(&file_type))
-        //                 && (conf.name.is_empty()
-        //                     || conf
-        //                         .name
-        //                         .iter()
-        //                         .any(|r| r.is_match(&entry.file_name().to_string_lossy())))
-        //             {
-        //                 println!("{}", entry.path().display())
-        //             }
-        //         }
-        //     }
-        // }
     }
-    Ok(())
+
+    /// whether this or any subexpression satisfies `pred`
+    fn any(&self, pred: &dyn Fn(&Expr) -> bool) -> bool {
+        pred(self)
+            || match self {
+                Expr::Not(expr) => expr.any(pred),
+                Expr::And(left, right) | Expr::Or(left, right) => left.any(pred) || right.any(pred),
+                _ => false,
+            }
+    }
+
+    /// lets every -contains search binary files too, for --binary
+    fn set_binary(&mut self) {
+        match self {
+            Expr::Contains(contains) => contains.binary = true,
+            Expr::Not(expr) => expr.set_binary(),
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                left.set_binary();
+                right.set_binary();
+            }
+            _ => (),
+        }
+    }
+
+    /// `left -a right`, but with `right` first when only `left` reads file
+    /// contents and neither has side effects
+    fn and(left: Expr, right: Expr) -> Expr {
+        let reads = |e: &Expr| e.any(&|e| matches!(e, Expr::Contains(_)));
+        let pure = |e: &Expr| !e.any(&|e| e.is_action() || matches!(e, Expr::Prune));
+        if reads(&left) && !reads(&right) && pure(&left) && pure(&right) {
+            Expr::And(Box::new(right), Box::new(left))
+        } else {
+            Expr::And(Box::new(left), Box::new(right))
+        }
+    }
+
+    fn is_action(&self) -> bool {
+        matches!(
+            self,
+            Expr::Print
+                | Expr::Print0
+                | Expr::Printf(_)
+                | Expr::Ls
+                | Expr::Exec(_)
+                | Expr::Delete(_)
+                | Expr::Tree(_)
+        )
+    }
+
+    /// Turns each -print into one collecting paths for --tree.
+    fn into_tree(self) -> Expr {
+        match self {
+            Expr::Not(expr) => Expr::Not(Box::new(expr.into_tree())),
+            Expr::And(left, right) => {
+                Expr::And(Box::new(left.into_tree()), Box::new(right.into_tree()))
+            }
+            Expr::Or(left, right) => {
+                Expr::Or(Box::new(left.into_tree()), Box::new(right.into_tree()))
+            }
+            Expr::Print => Expr::Tree(RefCell::default()),
+            expr => expr,
+        }
+    }
+
+    /// prints and forgets what each --tree -print collected under `root`
+    fn print_trees(&self, root: &str) -> io::Result<()> {
+        match self {
+            Expr::Not(expr) => expr.print_trees(root),
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                left.print_trees(root)?;
+                right.print_trees(root)
+            }
+            Expr::Tree(paths) => {
+                let paths = paths.take();
+                if paths.is_empty() {
+                    return Ok(());
+                }
+                let mut tree = Tree::default();
+                for path in &paths {
+                    tree.insert(path.strip_prefix(root).unwrap_or(path));
+                }
+                let mut out = [&os_bytes(OsStr::new(root))[..], b"\n"].concat();
+                tree.write("", &mut out);
+                write_out(&out)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// runs the batches still waiting, returning false if any action failed
+    fn finish(&self) -> bool {
+        match self {
+            Expr::Not(expr) => expr.finish(),
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                // no short circuit: every batch has to run
+                let left = left.finish();
+                right.finish() && left
+            }
+            Expr::Exec(exec) => {
+                exec.flush();
+                !exec.failed.get()
+            }
+            Expr::Delete(failed) => !failed.get(),
+            _ => true,
+        }
+    }
+}
+
+impl Exec {
+    fn eval(&self, entry: &Entry) -> bool {
+        let (dir, path) = if self.in_dir {
+            let dir = match entry.path().parent() {
+                Some(parent) if parent != Path::new("") => parent,
+                _ => Path::new("."),
+            };
+            let mut name = OsString::from("./");
+            name.push(entry.file_name());
+            (Some(dir), name)
+        } else {
+            (None, entry.path().as_os_str().to_owned())
+        };
+
+        let Some(batch) = &self.batch else {
+            let argv: Vec<_> = self
+                .command
+                .iter()
+                .map(|arg| replace_braces(arg, &path))
+                .collect();
+            if self.confirm && !confirm(&argv[0], &path) {
+                return false;
+            }
+            return self.spawn(dir, &argv);
+        };
+
+        let size = path.len() + 1 + std::mem::size_of::<usize>();
+        let full = {
+            let batch = batch.borrow();
+            !batch.paths.is_empty()
+                && (batch.dir.as_deref() != dir || batch.size + size > self.room)
+        };
+        if full {
+            self.flush();
+        }
+        let mut batch = batch.borrow_mut();
+        batch.dir = dir.map(Path::to_path_buf);
+        batch.paths.push(path);
+        batch.size += size;
+        true
+    }
+
+    /// bytes left for paths once the environment and `command` are in
+    fn room(command: &[String]) -> usize {
+        let env: usize = std::env::vars_os()
+            .map(|(k, v)| k.len() + v.len() + 2 + std::mem::size_of::<usize>())
+            .sum();
+        let command: usize = command
+            .iter()
+            .map(|arg| arg.len() + 1 + std::mem::size_of::<usize>())
+            .sum();
+        arg_max().saturating_sub(env + command + 2048)
+    }
+
+    fn flush(&self) {
+        let Some(batch) = &self.batch else {
+            return;
+        };
+        let Batch { dir, paths, .. } = batch.take();
+        if paths.is_empty() {
+            return;
+        }
+        let argv: Vec<_> = self
+            .command
+            .iter()
+            .map(OsString::from)
+            .chain(paths)
+            .collect();
+        if !self.spawn(dir.as_deref(), &argv) {
+            self.failed.set(true);
+        }
+    }
+
+    /// runs `argv`, the command name first
+    fn spawn(&self, dir: Option<&Path>, argv: &[OsString]) -> bool {
+        // keep what was printed so far ahead of the command's output
+        let _ = io::stdout().flush();
+        let mut cmd = Command::new(&argv[0]);
+        cmd.args(&argv[1..]);
+        if let Some(dir) = dir {
+            cmd.current_dir(dir);
+        }
+        match cmd.status() {
+            Ok(status) => status.success(),
+            Err(e) => {
+                eprintln!("{}: {}", argv[0].to_string_lossy(), e);
+                self.failed.set(true);
+                false
+            }
+        }
+    }
+}
+
+/// every `{}` in `arg` replaced by `path`
+fn replace_braces(arg: &str, path: &OsStr) -> OsString {
+    let mut parts = arg.split("{}");
+    let mut replaced = OsString::from(parts.next().unwrap_or_default());
+    for part in parts {
+        replaced.push(path);
+        replaced.push(part);
+    }
+    replaced
+}
+
+/// prompts like find's -ok, true if the answer starts with y
+fn confirm(command: &OsStr, path: &OsStr) -> bool {
+    eprint!(
+        "< {} ... {} > ? ",
+        command.to_string_lossy(),
+        path.to_string_lossy()
+    );
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).is_ok() && answer.trim_start().starts_with(['y', 'Y'])
+}
+
+/// the regex matching what the shell pattern `glob` matches, as fnmatch(3)
+/// without flags: `*` and `?` also match `/` and leading dots
+fn glob(glob: &str, ignore_case: bool) -> MyResult<Regex> {
+    let mut regex = String::from(if ignore_case { "(?si)^" } else { "(?s)^" });
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '\\' => regex.push_str(&regex::escape(&chars.next().unwrap_or('\\').to_string())),
+            '[' => match bracket(chars.clone()) {
+                Some((class, rest)) => {
+                    regex.push_str(&class);
+                    chars = rest;
+                }
+                None => regex.push_str("\\["),
+            },
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Ok(Regex::new(&regex)?)
+}
+
+/// the regex class for a bracket expression whose `[` was just read, and the
+/// characters after its `]`; `None` when it is not closed
+fn bracket(mut chars: Peekable<std::str::Chars>) -> Option<(String, Peekable<std::str::Chars>)> {
+    let mut class = String::from("[");
+    if chars.next_if(|c| *c == '!' || *c == '^').is_some() {
+        class.push('^');
+    }
+    let mut first = true;
+    loop {
+        let c = chars.next()?;
+        match c {
+            ']' if !first => break,
+            // character classes like [:alpha:] carry over to the regex as they are
+            '[' if chars.peek() == Some(&':') => {
+                class.push_str("[:");
+                chars.next();
+                loop {
+                    let c = chars.next()?;
+                    class.push(c);
+                    if c == ']' {
+                        break;
+                    }
+                }
+            }
+            '-' if !first && chars.peek() != Some(&']') => class.push('-'),
+            c if c.is_ascii_punctuation() => {
+                class.push('\\');
+                class.push(c);
+            }
+            c => class.push(c),
+        }
+        first = false;
+    }
+    class.push(']');
+    Some((class, chars))
+}
+
+fn write_out(bytes: &[u8]) -> io::Result<()> {
+    io::stdout().lock().write_all(bytes)
+}
+
+/// the paths --tree prints, as names nested in the order they came, with
+/// the directories between them and the starting point filled in
+#[derive(Default)]
+struct Tree {
+    children: Vec<(OsString, Tree)>,
+    index: HashMap<OsString, usize>,
+}
+
+impl Tree {
+    fn insert(&mut self, path: &Path) {
+        let mut tree = self;
+        for part in path.components() {
+            let name = part.as_os_str().to_os_string();
+            let i = match tree.index.get(&name) {
+                Some(&i) => i,
+                None => {
+                    tree.index.insert(name.clone(), tree.children.len());
+                    tree.children.push((name, Tree::default()));
+                    tree.children.len() - 1
+                }
+            };
+            tree = &mut tree.children[i].1;
+        }
+    }
+
+    fn write(&self, indent: &str, out: &mut Vec<u8>) {
+        for (i, (name, tree)) in self.children.iter().enumerate() {
+            let last = i + 1 == self.children.len();
+            let (branch, below) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            out.extend_from_slice(indent.as_bytes());
+            out.extend_from_slice(branch.as_bytes());
+            out.extend(os_bytes(name));
+            out.push(b'\n');
+            tree.write(&format!("{}{}", indent, below), out);
+        }
+    }
+}
+
+/// the name as it is on disk where possible
+#[cfg(unix)]
+fn os_bytes(s: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    s.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn os_bytes(s: &OsStr) -> Vec<u8> {
+    s.to_string_lossy().into_owned().into_bytes()
+}
+
+fn parse_format(format: &str) -> MyResult<Vec<Piece>> {
+    let mut pieces = vec![];
+    let mut text = vec![];
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => text.push(b'\n'),
+                Some('t') => text.push(b'\t'),
+                Some('r') => text.push(b'\r'),
+                Some('0') => text.push(b'\0'),
+                Some('a') => text.push(0x07),
+                Some('b') => text.push(0x08),
+                Some('f') => text.push(0x0c),
+                Some('v') => text.push(0x0b),
+                Some('\\') => text.push(b'\\'),
+                Some(other) => text.extend(format!("\\{}", other).bytes()),
+                None => text.push(b'\\'),
+            },
+            '%' if chars.peek() == Some(&'%') => {
+                chars.next();
+                text.push(b'%');
+            }
+            '%' => {
+                let left = chars.next_if_eq(&'-').is_some();
+                let mut width = 0;
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    width = width * 10 + digit as usize - '0' as usize;
+                }
+                let letter = chars.next().unwrap_or('%');
+                let field = if "TAC".contains(letter) {
+                    chars.next().filter(|k| time_format(*k).is_some())
+                } else {
+                    "pfhsmMugindylitac".contains(letter).then_some(' ')
+                };
+                let Some(field) = field else {
+                    return Err(From::from(format!(
+                        "invalid directive '%{}' in -printf format",
+                        letter
+                    )));
+                };
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Directive {
+                    letter,
+                    field: (field != ' ').then_some(field),
+                    width,
+                    left,
+                });
+            }
+            _ => text.extend(c.to_string().bytes()),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+/// the strftime format of a %Tk field, fractions of seconds printed like find's
+fn time_format(k: char) -> Option<&'static str> {
+    Some(match k {
+        '@' => "%s.%f0",
+        '+' => "%Y-%m-%d+%H:%M:%S.%f0",
+        'S' => "%S.%f0",
+        'T' => "%H:%M:%S.%f0",
+        'Y' => "%Y",
+        'y' => "%y",
+        'm' => "%m",
+        'd' => "%d",
+        'e' => "%e",
+        'j' => "%j",
+        'H' => "%H",
+        'I' => "%I",
+        'M' => "%M",
+        'p' => "%p",
+        'Z' => "%Z",
+        'a' => "%a",
+        'A' => "%A",
+        'b' | 'h' => "%b",
+        'B' => "%B",
+        'D' => "%D",
+        'F' => "%F",
+        'X' => "%X",
+        _ => return None,
+    })
+}
+
+fn printf(pieces: &[Piece], visit: &Visit) -> Vec<u8> {
+    let mut out = vec![];
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => out.extend(text),
+            Piece::Directive {
+                letter,
+                field,
+                width,
+                left,
+            } => {
+                let value = directive(visit, *letter, *field);
+                let pad = vec![b' '; width.saturating_sub(value.len())];
+                if *left {
+                    out.extend(value);
+                    out.extend(pad);
+                } else {
+                    out.extend(pad);
+                    out.extend(value);
+                }
+            }
+        }
+    }
+    out
+}
+
+fn directive(visit: &Visit, letter: char, field: Option<char>) -> Vec<u8> {
+    let entry = visit.entry;
+    let path = entry.path();
+    let text = match letter {
+        'p' => return os_bytes(path.as_os_str()),
+        'f' => return os_bytes(entry.file_name()),
+        'h' => {
+            return match path.parent() {
+                Some(parent) if parent != Path::new("") => os_bytes(parent.as_os_str()),
+                _ => b".".to_vec(),
+            }
+        }
+        'l' if entry.path_is_symlink() => {
+            return fs::read_link(path).map_or(vec![], |target| os_bytes(target.as_os_str()))
+        }
+        'l' => String::new(),
+        'd' => entry.depth().to_string(),
+        'y' => type_char(entry.file_type()).to_string(),
+        _ => {
+            let Some(metadata) = visit.metadata() else {
+                return vec![];
+            };
+            let stat = Stat::new(metadata);
+            match letter {
+                's' => metadata.len().to_string(),
+                'm' => format!("{:o}", stat.mode & 0o7777),
+                'M' => symbolic_mode(entry.file_type(), stat.mode),
+                'u' => user_name(stat.uid),
+                'g' => group_name(stat.gid),
+                'i' => stat.ino.to_string(),
+                'n' => stat.nlink.to_string(),
+                _ => {
+                    let time = match letter {
+                        'a' | 'A' => TimeField::Accessed,
+                        'c' | 'C' => TimeField::Changed,
+                        _ => TimeField::Modified,
+                    };
+                    let format = field
+                        .and_then(time_format)
+                        .unwrap_or("%a %b %e %H:%M:%S.%f0 %Y");
+                    time.get(metadata).map_or(String::new(), |t| {
+                        DateTime::<Local>::from(t).format(format).to_string()
+                    })
+                }
+            }
+        }
+    };
+    text.into_bytes()
+}
+
+/// a line like find's -ls
+fn ls(visit: &Visit) -> Vec<u8> {
+    let entry = visit.entry;
+    let Some(metadata) = visit.metadata() else {
+        return vec![];
+    };
+    let stat = Stat::new(metadata);
+    let modified = metadata.modified().map(DateTime::<Local>::from);
+    // like ls, the year instead of the time for anything older than six months
+    let recent = |t: &DateTime<Local>| (Local::now() - *t).num_days().abs() < 183;
+    let date = match modified {
+        Ok(t) if recent(&t) => t.format("%b %e %H:%M").to_string(),
+        Ok(t) => t.format("%b %e  %Y").to_string(),
+        Err(_) => String::new(),
+    };
+    let mut line = format!(
+        "{:>9} {:>6} {} {:>3} {:<8} {:<8} {:>8} {} ",
+        stat.ino,
+        stat.blocks.div_ceil(2),
+        symbolic_mode(entry.file_type(), stat.mode),
+        stat.nlink,
+        user_name(stat.uid),
+        group_name(stat.gid),
+        metadata.len(),
+        date
+    )
+    .into_bytes();
+    line.extend(os_bytes(entry.path().as_os_str()));
+    if entry.path_is_symlink() {
+        if let Ok(target) = fs::read_link(entry.path()) {
+            line.extend(b" -> ");
+            line.extend(os_bytes(target.as_os_str()));
+        }
+    }
+    line.push(b'\n');
+    line
+}
+
+/// the stat(2) fields without a portable accessor
+struct Stat {
+    dev: u64,
+    mode: u32,
+    ino: u64,
+    nlink: u64,
+    uid: u32,
+    gid: u32,
+    /// in 512-byte units
+    blocks: u64,
+}
+
+impl Stat {
+    #[cfg(unix)]
+    fn new(metadata: &Metadata) -> Stat {
+        use std::os::unix::fs::MetadataExt;
+        Stat {
+            dev: metadata.dev(),
+            mode: metadata.mode(),
+            ino: metadata.ino(),
+            nlink: metadata.nlink(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            blocks: metadata.blocks(),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn new(metadata: &Metadata) -> Stat {
+        let write = if metadata.permissions().readonly() {
+            0
+        } else {
+            0o200
+        };
+        let exec = if metadata.is_dir() { 0o111 } else { 0 };
+        Stat {
+            dev: 0,
+            mode: 0o444 | write | exec,
+            ino: 0,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            blocks: metadata.len().div_ceil(512),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn user_name(uid: u32) -> String {
+    uzers::get_user_by_uid(uid)
+        .map(|u| u.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| uid.to_string())
+}
+
+#[cfg(unix)]
+fn group_name(gid: u32) -> String {
+    uzers::get_group_by_gid(gid)
+        .map(|g| g.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| gid.to_string())
+}
+
+#[cfg(unix)]
+fn user_id(name: &str) -> Option<u32> {
+    uzers::get_user_by_name(name).map(|u| u.uid())
+}
+
+#[cfg(unix)]
+fn group_id(name: &str) -> Option<u32> {
+    uzers::get_group_by_name(name).map(|g| g.gid())
+}
+
+#[cfg(unix)]
+fn has_user(uid: u32) -> bool {
+    uzers::get_user_by_uid(uid).is_some()
+}
+
+#[cfg(unix)]
+fn has_group(gid: u32) -> bool {
+    uzers::get_group_by_gid(gid).is_some()
+}
+
+#[cfg(not(unix))]
+fn user_name(uid: u32) -> String {
+    uid.to_string()
+}
+
+#[cfg(not(unix))]
+fn group_name(gid: u32) -> String {
+    gid.to_string()
+}
+
+#[cfg(not(unix))]
+fn user_id(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(not(unix))]
+fn group_id(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(not(unix))]
+fn has_user(_uid: u32) -> bool {
+    true
+}
+
+#[cfg(not(unix))]
+fn has_group(_gid: u32) -> bool {
+    true
+}
+
+/// an octal mode, or a symbolic one like `u+w,go=rx` applied to no bits
+fn parse_mode(mode: &str) -> Option<u32> {
+    if !mode.is_empty() && mode.chars().all(|c| c.is_digit(8)) {
+        return u32::from_str_radix(mode, 8)
+            .ok()
+            .filter(|bits| *bits <= 0o7777);
+    }
+    let mut bits = 0;
+    for clause in mode.split(',') {
+        let ops = clause.find(['+', '-', '='])?;
+        let who = clause[..ops].chars().try_fold(0, |who, c| {
+            Some(
+                who | match c {
+                    'u' => 0o4700,
+                    'g' => 0o2070,
+                    'o' => 0o1007,
+                    'a' => 0o7777,
+                    _ => return None,
+                },
+            )
+        })?;
+        let who = if who == 0 { 0o7777 } else { who };
+        let mut rest = &clause[ops..];
+        while let Some(op) = rest.chars().next() {
+            let end = rest[1..]
+                .find(['+', '-', '='])
+                .map_or(rest.len(), |i| i + 1);
+            let perms = rest[1..end].chars().try_fold(0, |perms, c| {
+                Some(
+                    perms
+                        | match c {
+                            'r' => 0o444,
+                            'w' => 0o222,
+                            'x' | 'X' => 0o111,
+                            's' => 0o6000,
+                            't' => 0o1000,
+                            _ => return None,
+                        },
+                )
+            })? & who;
+            match op {
+                '+' => bits |= perms,
+                '-' => bits &= !perms,
+                _ => bits = bits & !who | perms,
+            }
+            rest = &rest[end..];
+        }
+    }
+    Some(bits)
+}
+
+/// find's %y letter for the entry's type
+fn type_char(file_type: FileType) -> char {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_fifo() {
+            return 'p';
+        } else if file_type.is_socket() {
+            return 's';
+        } else if file_type.is_block_device() {
+            return 'b';
+        } else if file_type.is_char_device() {
+            return 'c';
+        }
+    }
+    if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_dir() {
+        'd'
+    } else {
+        'f'
+    }
+}
+
+/// a mode like "-rwxr-sr-t", as ls prints it
+fn symbolic_mode(file_type: FileType, mode: u32) -> String {
+    let bit = |mask, c| if mode & mask != 0 { c } else { '-' };
+    let exec = |mask, special, c: char| match (mode & mask != 0, mode & special != 0) {
+        (true, true) => c,
+        (false, true) => c.to_ascii_uppercase(),
+        (true, false) => 'x',
+        (false, false) => '-',
+    };
+    let kind = match type_char(file_type) {
+        'f' => '-',
+        c => c,
+    };
+    [
+        kind,
+        bit(0o400, 'r'),
+        bit(0o200, 'w'),
+        exec(0o100, 0o4000, 's'),
+        bit(0o040, 'r'),
+        bit(0o020, 'w'),
+        exec(0o010, 0o2000, 's'),
+        bit(0o004, 'r'),
+        bit(0o002, 'w'),
+        exec(0o001, 0o1000, 't'),
+    ]
+    .iter()
+    .collect()
+}
+
+/// -contains and -contains-fixed
+#[derive(Debug)]
+struct Contains {
+    regex: regex::bytes::Regex,
+    /// search files with a NUL in their first block as well
+    binary: bool,
+}
+
+impl Contains {
+    fn eval(&self, entry: &Entry) -> bool {
+        if !entry.file_type().is_file() {
+            return false;
+        }
+        self.search(entry.path()).unwrap_or_else(|e| {
+            eprintln!("{}: {}", entry.path().display(), e);
+            false
+        })
+    }
+
+    /// Matches line by line, without the line ending, up to the first match.
+    fn search(&self, path: &Path) -> io::Result<bool> {
+        let mut reader = BufReader::new(fs::File::open(path)?);
+        if !self.binary && reader.fill_buf()?.contains(&0) {
+            return Ok(false);
+        }
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line)? > 0 {
+            let text = line.strip_suffix(b"\n").unwrap_or(&line);
+            if self
+                .regex
+                .is_match(text.strip_suffix(b"\r").unwrap_or(text))
+            {
+                return Ok(true);
+            }
+            line.clear();
+        }
+        Ok(false)
+    }
+}
+
+/// recursive descent over `or := and (-o and)*`, `and := unary ([-a] unary)*`,
+/// `unary := ! unary | ( or ) | primary`
+struct ExprParser<'a> {
+    tokens: Peekable<Iter<'a, String>>,
+    command: &'a clap::Command,
+    /// options met between tests, for clap
+    options: Vec<String>,
+}
+
+impl ExprParser<'_> {
+    /// set aside the options, and their values, where a test could be
+    fn take_options(&mut self) {
+        loop {
+            let rest: Vec<String> = self.tokens.clone().cloned().collect();
+            let Some(n) = option_values(self.command, &rest) else {
+                return;
+            };
+            self.options
+                .extend(self.tokens.by_ref().take(n + 1).cloned());
+        }
+    }
+
+    fn peek(&mut self) -> Option<&str> {
+        self.tokens.peek().map(|s| s.as_str())
+    }
+
+    fn next(&mut self) -> Option<&str> {
+        self.tokens.next().map(|s| s.as_str())
+    }
+
+    fn or(&mut self) -> MyResult<Expr> {
+        let mut left = self.and()?;
+        self.take_options();
+        while matches!(self.peek(), Some("-o" | "-or")) {
+            self.next();
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> MyResult<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            self.take_options();
+            match self.peek() {
+                None | Some(")" | "-o" | "-or") => return Ok(left),
+                Some("-a" | "-and") => {
+                    self.next();
+                }
+                Some(_) => {}
+            }
+            left = Expr::and(left, self.unary()?);
+        }
+    }
+
+    fn unary(&mut self) -> MyResult<Expr> {
+        self.take_options();
+        match self.next() {
+            None => Err(From::from(
+                "invalid expression; expected an expression at the end",
+            )),
+            Some("!" | "-not") => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some("(") => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(")") => Ok(expr),
+                    _ => Err(From::from("invalid expression; missing ')'")),
+                }
+            }
+            Some(token) => {
+                let token = token.to_string();
+                self.primary(&token)
+            }
+        }
+    }
+
+    fn primary(&mut self, token: &str) -> MyResult<Expr> {
+        match token {
+            "-true" => Ok(Expr::True),
+            "-false" => Ok(Expr::False),
+            "-name" | "-iname" => Ok(Expr::Name(glob(self.argument(token)?, token == "-iname")?)),
+            "-path" | "-ipath" => Ok(Expr::Path(glob(self.argument(token)?, token == "-ipath")?)),
+            "-regex" | "-iregex" => {
+                let flags = if token == "-iregex" { "(?i)" } else { "" };
+                let regex = format!("{}^(?:{})$", flags, self.argument(token)?);
+                Ok(Expr::Path(Regex::new(&regex)?))
+            }
+            "-type" => {
+                let types = self.argument(token)?.to_string();
+                types
+                    .split(',')
+                    .map(|t| {
+                        EntryType::from_str(t, false)
+                            .map(Expr::Type)
+                            .map_err(|_| format!("unknown argument to -type: {}", t).into())
+                    })
+                    .collect::<MyResult<Vec<_>>>()
+                    .map(|types| any_of(types.into_iter()).unwrap_or_default())
+            }
+            "-size" => {
+                let arg = self.argument(token)?;
+                let unit = match Cmp::parse(arg) {
+                    Some((cmp, "" | "b")) => Some((cmp, 512)),
+                    Some((cmp, "c")) => Some((cmp, 1)),
+                    Some((cmp, "w")) => Some((cmp, 2)),
+                    Some((cmp, "k")) => Some((cmp, 1 << 10)),
+                    Some((cmp, "M")) => Some((cmp, 1 << 20)),
+                    Some((cmp, "G")) => Some((cmp, 1 << 30)),
+                    _ => None,
+                };
+                unit.map(|(cmp, unit)| Expr::Size(cmp, unit))
+                    .ok_or_else(|| format!("invalid argument '{}' to '-size'", arg).into())
+            }
+            "-mtime" | "-atime" | "-ctime" | "-mmin" | "-amin" | "-cmin" => {
+                let field = match &token[1..2] {
+                    "a" => TimeField::Accessed,
+                    "c" => TimeField::Changed,
+                    _ => TimeField::Modified,
+                };
+                let unit = if token.ends_with("min") {
+                    60
+                } else {
+                    24 * 60 * 60
+                };
+                Ok(Expr::Time(
+                    field,
+                    self.number(token)?,
+                    unit,
+                    SystemTime::now(),
+                ))
+            }
+            "-newer" => {
+                let file = self.argument(token)?;
+                let modified = fs::metadata(file)
+                    .and_then(|m| m.modified())
+                    .map_err(|e| format!("{}: {}", file, e))?;
+                Ok(Expr::Newer(modified))
+            }
+            "-empty" => Ok(Expr::Empty),
+            "-contains" | "-contains-fixed" => {
+                let arg = self.argument(token)?;
+                let pattern = if token == "-contains-fixed" {
+                    regex::escape(arg)
+                } else {
+                    arg.to_string()
+                };
+                Ok(Expr::Contains(Contains {
+                    regex: regex::bytes::Regex::new(&pattern)?,
+                    binary: false,
+                }))
+            }
+            "-perm" => {
+                let arg = self.argument(token)?;
+                let (how, mode) = match arg.as_bytes().first() {
+                    Some(b'-') => (PermMatch::All, &arg[1..]),
+                    Some(b'/') => (PermMatch::Any, &arg[1..]),
+                    _ => (PermMatch::Exactly, arg),
+                };
+                parse_mode(mode)
+                    .map(|bits| Expr::Perm(how, bits))
+                    .ok_or_else(|| format!("invalid mode '{}'", arg).into())
+            }
+            "-user" => {
+                let arg = self.argument(token)?;
+                user_id(arg)
+                    .or_else(|| arg.parse().ok())
+                    .map(|uid| Expr::Uid(Cmp::Exactly(uid.into())))
+                    .ok_or_else(|| {
+                        format!("-user: '{}' is not the name of a known user", arg).into()
+                    })
+            }
+            "-group" => {
+                let arg = self.argument(token)?;
+                group_id(arg)
+                    .or_else(|| arg.parse().ok())
+                    .map(|gid| Expr::Gid(Cmp::Exactly(gid.into())))
+                    .ok_or_else(|| {
+                        format!("-group: '{}' is not the name of an existing group", arg).into()
+                    })
+            }
+            "-uid" => Ok(Expr::Uid(self.number(token)?)),
+            "-gid" => Ok(Expr::Gid(self.number(token)?)),
+            "-nouser" => Ok(Expr::NoUser),
+            "-nogroup" => Ok(Expr::NoGroup),
+            "-inum" => Ok(Expr::Inum(self.number(token)?)),
+            "-links" => Ok(Expr::Links(self.number(token)?)),
+            "-samefile" => {
+                let file = self.argument(token)?;
+                let stat = fs::symlink_metadata(file)
+                    .map(|m| Stat::new(&m))
+                    .map_err(|e| format!("{}: {}", file, e))?;
+                Ok(Expr::SameFile(stat.dev, stat.ino))
+            }
+            "-print" => Ok(Expr::Print),
+            "-print0" => Ok(Expr::Print0),
+            "-printf" => Ok(Expr::Printf(parse_format(self.argument(token)?)?)),
+            "-ls" => Ok(Expr::Ls),
+            "-prune" => Ok(Expr::Prune),
+            "-depth" => Ok(Expr::Depth),
+            "-xdev" | "-mount" => Ok(Expr::Xdev),
+            "-delete" => Ok(Expr::Delete(Cell::default())),
+            "-exec" | "-execdir" | "-ok" | "-okdir" => {
+                let confirm = token.starts_with("-ok");
+                let mut command = vec![];
+                let batched = loop {
+                    match self.next() {
+                        None => return Err(format!("missing argument to '{}'", token).into()),
+                        Some(";") => break false,
+                        Some("+") if !confirm && command.last().is_some_and(|a| a == "{}") => {
+                            command.pop();
+                            break true;
+                        }
+                        Some(arg) => command.push(arg.to_string()),
+                    }
+                };
+                if command.is_empty() {
+                    return Err(format!("missing argument to '{}'", token).into());
+                }
+                if batched && command.iter().any(|arg| arg.contains("{}")) {
+                    return Err(format!(
+                        "only one instance of {{}} is supported with {} ... +",
+                        token
+                    )
+                    .into());
+                }
+                Ok(Expr::Exec(Exec {
+                    room: if batched { Exec::room(&command) } else { 0 },
+                    command,
+                    in_dir: token.ends_with("dir"),
+                    confirm,
+                    batch: batched.then(RefCell::default),
+                    failed: Cell::default(),
+                }))
+            }
+            ")" => Err(From::from("invalid expression; you have too many ')'")),
+            "-a" | "-and" | "-o" | "-or" => Err(From::from(format!(
+                "invalid expression; you have used a binary operator '{}' with nothing before it.",
+                token
+            ))),
+            _ => Err(From::from(format!("unknown predicate '{}'", token))),
+        }
+    }
+
+    /// the `[+-]N` argument of `token`
+    fn number(&mut self, token: &str) -> MyResult<Cmp> {
+        let arg = self.argument(token)?;
+        match Cmp::parse(arg) {
+            Some((cmp, "")) => Ok(cmp),
+            _ => Err(From::from(format!(
+                "invalid argument '{}' to '{}'",
+                arg, token
+            ))),
+        }
+    }
+
+    fn argument(&mut self, token: &str) -> MyResult<&str> {
+        self.next()
+            .ok_or_else(|| format!("missing argument to '{}'", token).into())
+    }
+}
+
+/// Returns false when an action failed or a file system loop was found, for
+/// find's exit status of 1.
+pub fn run(conf: Config) -> MyResult<bool> {
+    let mut paths = Vec::new();
+    let mut ok = true;
+    let walk = Walk {
+        min_depth: conf.min_depth.unwrap_or(0),
+        max_depth: conf.max_depth.unwrap_or(usize::MAX),
+        contents_first: conf
+            .expr
+            .any(&|e| matches!(e, Expr::Depth | Expr::Delete(_))),
+        follow_links: conf.follow_links,
+        follow_root_links: conf.follow_links || conf.follow_root_links,
+        same_file_system: conf.expr.any(&|e| matches!(e, Expr::Xdev)),
+        sort: conf.sort.map(|key| Sort {
+            key,
+            reverse: conf.reverse,
+        }),
+        gitignore: conf.gitignore,
+        hidden: !conf.no_hidden,
+        stopped: AtomicBool::new(false),
+    };
+    let mut write_error = None;
+    for path in starting_points(&conf.path, &walk, &conf.expr) {
+        let mut counts = Counts::default();
+        let mut visit = |res: Result<Entry, WalkError>| {
+            if walk.is_stopped() {
+                return false;
+            }
+            let entry = match res {
+                Err(e) => {
+                    ok &= !e.is_loop;
+                    eprintln!("{}", e.message);
+                    return false;
+                }
+                Ok(entry) => entry,
+            };
+            let visit = Visit::new(&entry);
+            if conf.expr.eval(&visit) && conf.count {
+                counts.add(&visit);
+            }
+            if let Some(e) = visit.write_error.take() {
+                write_error = Some(e);
+                walk.stop();
+                return false;
+            }
+            !visit.prune.get()
+        };
+        match conf.jobs {
+            Some(jobs) => walk.parallel(path, jobs.into(), &mut visit),
+            None => walk.sequential(path, &mut visit),
+        }
+        if let Some(e) = write_error {
+            return Err(e.into());
+        }
+        conf.expr.print_trees(path)?;
+        paths.push(PathCounts { path, counts });
+        // for entry in WalkDir::new(path) {
+        //     match entry {
+        //         Err(e) => eprintln!("{}", e),
+        //         Ok(entry) => {
+        //             let file_type = if entry.file_type().is_dir() {
+        //                 Dir
+        //             } else if entry.file_type().is_file() {
+        //                 File
+        //             } else {
+        //                 Link
+        //             };
+        //             if (conf.entry_type.is_empty() || conf.entry_type.contains(&file_type))
+        //                 && (conf.name.is_empty()
+        //                     || conf
+        //                         .name
+        //                         .iter()
+        //                         .any(|r| r.is_match(&entry.file_name().to_string_lossy())))
+        //             {
+        //                 println!("{}", entry.path().display())
+        //             }
+        //         }
+        //     }
+        // }
+    }
+    let ok = conf.expr.finish() && ok;
+    if conf.count {
+        let total = Counts::total(paths.iter().map(|path| &path.counts));
+        Report { paths, total }.print(conf.count_format)?;
+    }
+    io::stdout().flush()?;
+    Ok(ok)
+}
+
+/// The paths to walk, for --sort=path in path order, without those another
+/// one walks through anyway, such as src/lib after src or src again. All are
+/// kept when `expr` has -prune, which can leave out of one walk what another
+/// takes in, or -depth, which would move what is left out.
+fn starting_points<'a>(paths: &'a [String], walk: &Walk, expr: &Expr) -> Vec<&'a str> {
+    let mut paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+    if let Some(sort) = walk.sort.filter(|sort| sort.key == SortKey::Path) {
+        paths.sort_by(|a, b| sort.apply(Path::new(a).cmp(Path::new(b))));
+    }
+    if walk.contents_first || expr.any(&|e| matches!(e, Expr::Prune)) {
+        return paths;
+    }
+    let real: Vec<_> = paths
+        .iter()
+        .map(|path| real_path(path, walk.follow_root_links))
+        .collect();
+    // the shallowest first, so that each is only checked against those kept
+    let mut order: Vec<usize> = (0..paths.len()).collect();
+    order.sort_by_key(|&i| (real[i].as_ref().map(|p| p.components().count()), i));
+    let mut kept: Vec<usize> = vec![];
+    for i in order {
+        let covered = real[i].as_ref().is_some_and(|inner| {
+            kept.iter()
+                .filter_map(|&j| real[j].as_ref())
+                .any(|outer| walk.reaches(outer, inner))
+        });
+        if !covered {
+            kept.push(i);
+        }
+    }
+    kept.sort_unstable();
+    kept.into_iter().map(|i| paths[i]).collect()
+}
+
+/// where a starting point really is, which for a link not followed is
+/// where the link itself is
+fn real_path(path: &str, follow: bool) -> Option<PathBuf> {
+    let path = Path::new(path);
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !follow => {
+            let parent = match parent.as_os_str().is_empty() {
+                true => Path::new("."),
+                false => parent,
+            };
+            fs::canonicalize(parent)
+                .ok()
+                .map(|parent| parent.join(name))
+        }
+        _ => fs::canonicalize(path).ok(),
+    }
+}
+
+/// how to walk from each starting point
+struct Walk {
+    min_depth: usize,
+    max_depth: usize,
+    contents_first: bool,
+    follow_links: bool,
+    follow_root_links: bool,
+    same_file_system: bool,
+    sort: Option<Sort>,
+    gitignore: bool,
+    hidden: bool,
+    /// set to end the walk early, as when stdout is gone
+    stopped: AtomicBool,
+}
+
+/// what --gitignore and --no-hidden leave out of a walk
+struct Filter {
+    hidden: bool,
+    ignores: Option<Ignores>,
+}
+
+impl Filter {
+    fn skips(&self, path: &Path, is_dir: bool) -> bool {
+        let name = path.file_name().unwrap_or_default();
+        (!self.hidden && name.to_string_lossy().starts_with('.'))
+            || self
+                .ignores
+                .as_ref()
+                .is_some_and(|ignores| ignores.ignored(path, is_dir))
+    }
+}
+
+/// the ignore files in effect in each directory of a walk, from the top of the
+/// git repository the walk starts in down
+struct Ignores {
+    /// the repository's top directory, or else the starting point
+    top: PathBuf,
+    /// .git/info/exclude and the global excludes only apply in a repository
+    repo: bool,
+    /// by absolute path, `None` for a directory without any ignore files above
+    layers: Mutex<HashMap<PathBuf, Option<Arc<Layer>>>>,
+}
+
+/// the ignore files of one directory, most important first, and the layer of
+/// the closest directory above with any
+struct Layer {
+    matchers: Vec<Gitignore>,
+    parent: Option<Arc<Layer>>,
+}
+
+impl Ignores {
+    fn new(root: &Path) -> Ignores {
+        let root = std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+        let repo = root.ancestors().find(|dir| dir.join(".git").exists());
+        Ignores {
+            top: repo.unwrap_or(&root).to_path_buf(),
+            repo: repo.is_some(),
+            layers: Mutex::default(),
+        }
+    }
+
+    /// Like git, the closest directory's ignore files decide, and within a
+    /// directory .ignore comes before .gitignore, .git/info/exclude and the
+    /// global excludes.
+    fn ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if is_dir && path.file_name() == Some(OsStr::new(".git")) {
+            return true;
+        }
+        let Ok(path) = std::path::absolute(path) else {
+            return false;
+        };
+        let mut layer = path.parent().and_then(|dir| self.layer(dir));
+        while let Some(current) = layer {
+            for matcher in &current.matchers {
+                match matcher.matched(&path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+            layer = current.parent.clone();
+        }
+        false
+    }
+
+    fn layer(&self, dir: &Path) -> Option<Arc<Layer>> {
+        if !dir.starts_with(&self.top) {
+            return None;
+        }
+        if let Some(layer) = self.layers.lock().unwrap().get(dir) {
+            return layer.clone();
+        }
+        let parent = match dir.parent() {
+            Some(parent) if dir != self.top => self.layer(parent),
+            _ => None,
+        };
+        let mut files = vec![dir.join(".ignore"), dir.join(".gitignore")];
+        if self.repo && dir == self.top {
+            files.push(dir.join(".git/info/exclude"));
+            files.extend(gitconfig_excludes_path());
+        }
+        let matchers: Vec<_> = files
+            .into_iter()
+            .filter(|file| file.is_file())
+            .filter_map(|file| {
+                // the patterns of all of them are relative to this directory
+                let mut builder = GitignoreBuilder::new(dir);
+                if let Some(e) = builder.add(&file) {
+                    eprintln!("{}: {}", file.display(), e);
+                }
+                builder
+                    .build()
+                    .map_err(|e| eprintln!("{}: {}", file.display(), e))
+                    .ok()
+            })
+            .collect();
+        let layer = if matchers.is_empty() {
+            parent
+        } else {
+            Some(Arc::new(Layer { matchers, parent }))
+        };
+        self.layers
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), layer.clone());
+        layer
+    }
+}
+
+/// called with each entry or error in walk order, returning false to skip
+/// the contents of a directory
+type VisitFn<'a> = dyn FnMut(Result<Entry, WalkError>) -> bool + 'a;
+
+/// a directory waiting to be read by the parallel walker
+struct Job {
+    id: usize,
+    dir: PathBuf,
+    depth: usize,
+    /// the directories above and their device and inode numbers, for finding
+    /// loops when following links
+    ancestors: Arc<Vec<(PathBuf, u64, u64)>>,
+}
+
+/// a directory's entries, each subdirectory with the id of the job reading
+/// it in turn, or the error reading the directory
+type Entries = Result<Vec<(Result<Entry, WalkError>, Option<usize>)>, WalkError>;
+
+/// a directory read by a worker
+struct Listing {
+    id: usize,
+    entries: Entries,
+}
+
+/// what the workers of one parallel walk share
+struct Pool {
+    injector: Injector<Job>,
+    stealers: Vec<Stealer<Job>>,
+    next_id: AtomicUsize,
+    /// jobs under a pruned directory, which need not be read
+    pruned: Mutex<HashSet<usize>>,
+    done: AtomicBool,
+    /// bumped whenever jobs are queued or the walk is done, for idle workers
+    /// to wait on
+    wakeups: Mutex<usize>,
+    woken: Condvar,
+    /// the device of the starting point, for -xdev
+    root_dev: u64,
+    filter: Filter,
+}
+
+impl Pool {
+    fn wake(&self) {
+        *self.wakeups.lock().unwrap() += 1;
+        self.woken.notify_all();
+    }
+
+    /// waits for a wakeup after the `seen`th
+    fn sleep(&self, seen: usize) {
+        let mut wakeups = self.wakeups.lock().unwrap();
+        while *wakeups == seen && !self.done.load(Ordering::Relaxed) {
+            wakeups = self.woken.wait(wakeups).unwrap();
+        }
+    }
+}
+
+impl Walk {
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    fn filter(&self, root: &Path) -> Filter {
+        Filter {
+            hidden: self.hidden,
+            ignores: self.gitignore.then(|| Ignores::new(root)),
+        }
+    }
+
+    /// Whether a walk from `outer` goes through `inner` and all that is below
+    /// it, both real paths, leaving -prune aside.
+    fn reaches(&self, outer: &Path, inner: &Path) -> bool {
+        let Ok(rest) = inner.strip_prefix(outer) else {
+            return false;
+        };
+        if rest.as_os_str().is_empty() {
+            return true;
+        }
+        // otherwise the walk from inner goes deeper
+        if self.max_depth != usize::MAX {
+            return false;
+        }
+        let dev = |path: &Path| fs::metadata(path).ok().map(|m| Stat::new(&m).dev);
+        if self.same_file_system && dev(outer) != dev(inner) {
+            return false;
+        }
+        let filter = self.filter(outer);
+        let mut path = outer.to_path_buf();
+        rest.components().all(|part| {
+            path.push(part);
+            !filter.skips(&path, path.is_dir())
+        })
+    }
+
+    fn sequential(&self, root: &str, visit: &mut VisitFn) {
+        let filter = self.filter(Path::new(root));
+        let mut walker = WalkDir::new(root)
+            .min_depth(self.min_depth)
+            .max_depth(self.max_depth)
+            .contents_first(self.contents_first)
+            .follow_links(self.follow_links)
+            .follow_root_links(self.follow_root_links)
+            .same_file_system(self.same_file_system);
+        if let Some(sort) = self.sort {
+            walker = walker.sort_by(move |a, b| sort.compare(a, b));
+        }
+        // starting points are never left out
+        let mut walker = walker
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !filter.skips(e.path(), e.file_type().is_dir()));
+        while let Some(res) = walker.next() {
+            let res = res.map(Entry::from).map_err(WalkError::from);
+            let is_dir = matches!(&res, Ok(entry) if entry.file_type().is_dir());
+            // after its contents, as with -depth, there is nothing left to prune
+            if !visit(res) && is_dir && !self.contents_first {
+                walker.skip_current_dir();
+            }
+            if self.is_stopped() {
+                return;
+            }
+        }
+    }
+
+    /// Workers read directories from a work-stealing queue while this thread
+    /// evaluates what they found, so actions still run one at a time. Entries
+    /// come in the order they are read unless sorted or contents first, when
+    /// this thread waits for each directory in walk order.
+    fn parallel(&self, root: &str, threads: usize, visit: &mut VisitFn) {
+        let entry = match Entry::root(root, self.follow_root_links) {
+            Ok(entry) => entry,
+            Err(e) => {
+                visit(Err(e));
+                return;
+            }
+        };
+        if !entry.file_type().is_dir() || self.max_depth == 0 {
+            if self.min_depth == 0 {
+                visit(Ok(entry));
+            }
+            return;
+        }
+        let stat = entry.metadata().map(|m| Stat::new(&m));
+        let (dev, ino) = stat.map_or((0, 0), |s| (s.dev, s.ino));
+        let workers: Vec<_> = (0..threads).map(|_| Worker::new_lifo()).collect();
+        let pool = Pool {
+            injector: Injector::new(),
+            stealers: workers.iter().map(Worker::stealer).collect(),
+            next_id: AtomicUsize::new(1),
+            pruned: Mutex::default(),
+            done: AtomicBool::new(false),
+            wakeups: Mutex::new(0),
+            woken: Condvar::new(),
+            root_dev: dev,
+            filter: self.filter(Path::new(root)),
+        };
+        pool.injector.push(Job {
+            id: 0,
+            dir: entry.path().to_path_buf(),
+            depth: 0,
+            ancestors: Arc::new(vec![(entry.path().to_path_buf(), dev, ino)]),
+        });
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|scope| {
+            for worker in workers {
+                let (pool, tx) = (&pool, tx.clone());
+                scope.spawn(move || self.work(pool, worker, tx));
+            }
+            drop(tx);
+            let mut listings = Listings {
+                rx,
+                pool: &pool,
+                waiting: HashMap::new(),
+            };
+            if self.sort.is_some() || self.contents_first {
+                self.in_order(entry, Some(0), &mut listings, visit);
+            } else {
+                self.as_read(entry, &mut listings, visit);
+            }
+            pool.done.store(true, Ordering::Relaxed);
+            pool.wake();
+        });
+    }
+
+    fn work(&self, pool: &Pool, local: Worker<Job>, tx: Sender<Listing>) {
+        while !pool.done.load(Ordering::Relaxed) {
+            // taken before looking, so that jobs queued after it wake us
+            let seen = *pool.wakeups.lock().unwrap();
+            let job = local.pop().or_else(|| {
+                std::iter::repeat_with(|| {
+                    pool.injector
+                        .steal_batch_and_pop(&local)
+                        .or_else(|| pool.stealers.iter().map(Stealer::steal).collect())
+                })
+                .find(|steal| !steal.is_retry())
+                .and_then(|steal| steal.success())
+            });
+            let Some(job) = job else {
+                pool.sleep(seen);
+                continue;
+            };
+            let entries = if pool.pruned.lock().unwrap().contains(&job.id) {
+                Ok(vec![])
+            } else {
+                self.read(&job, pool, &local)
+            };
+            if tx
+                .send(Listing {
+                    id: job.id,
+                    entries,
+                })
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+
+    /// the entries of the job's directory, queueing the subdirectories to
+    /// descend into
+    fn read(&self, job: &Job, pool: &Pool, local: &Worker<Job>) -> Entries {
+        let mut entries = vec![];
+        let mut queued = false;
+        for dirent in fs::read_dir(&job.dir).map_err(|e| WalkError::io(&job.dir, e))? {
+            let (path, file_type) = match dirent.and_then(|d| Ok((d.path(), d.file_type()?))) {
+                Ok(found) => found,
+                Err(e) => {
+                    entries.push((Err(WalkError::io(&job.dir, e)), None));
+                    continue;
+                }
+            };
+            let entry = Entry::new(path, file_type, job.depth + 1, self.follow_links);
+            if pool.filter.skips(entry.path(), entry.file_type().is_dir()) {
+                continue;
+            }
+            if !entry.file_type().is_dir() || entry.depth() >= self.max_depth {
+                entries.push((Ok(entry), None));
+                continue;
+            }
+            let (dev, ino) = if self.follow_links || self.same_file_system {
+                match entry.metadata() {
+                    Ok(m) => {
+                        let stat = Stat::new(&m);
+                        (stat.dev, stat.ino)
+                    }
+                    Err(e) => {
+                        entries.push((Err(WalkError::io(entry.path(), e)), None));
+                        continue;
+                    }
+                }
+            } else {
+                (0, 0)
+            };
+            let ancestor = job
+                .ancestors
+                .iter()
+                .find(|(_, d, i)| self.follow_links && cfg!(unix) && (*d, *i) == (dev, ino));
+            if let Some((ancestor, _, _)) = ancestor {
+                let message = format!(
+                    "File system loop found: {} points to an ancestor {}",
+                    entry.path().display(),
+                    ancestor.display()
+                );
+                let is_loop = true;
+                entries.push((Err(WalkError { message, is_loop }), None));
+                continue;
+            }
+            if self.same_file_system && dev != pool.root_dev {
+                entries.push((Ok(entry), None));
+                continue;
+            }
+            let id = pool.next_id.fetch_add(1, Ordering::Relaxed);
+            let mut ancestors = Vec::new();
+            if self.follow_links {
+                ancestors.extend(job.ancestors.iter().cloned());
+                ancestors.push((entry.path().to_path_buf(), dev, ino));
+            }
+            local.push(Job {
+                id,
+                dir: entry.path().to_path_buf(),
+                depth: entry.depth(),
+                ancestors: Arc::new(ancestors),
+            });
+            queued = true;
+            entries.push((Ok(entry), Some(id)));
+        }
+        if queued {
+            pool.wake();
+        }
+        Ok(entries)
+    }
+
+    /// visits the entries as the listings arrive, though never before the
+    /// directory holding them
+    fn as_read(&self, root: Entry, listings: &mut Listings, visit: &mut VisitFn) {
+        if self.min_depth == 0 && !visit(Ok(root)) {
+            listings.prune(0);
+            return;
+        }
+        // the directories visited and descended into whose listing is still
+        // to come; another worker can send a subdirectory's listing first
+        let mut expected = HashSet::from([0]);
+        while !expected.is_empty() {
+            let Ok(listing) = listings.rx.recv() else {
+                return;
+            };
+            if !expected.remove(&listing.id) {
+                if !listings.is_pruned(listing.id) {
+                    listings.waiting.insert(listing.id, listing);
+                } else if let Ok(entries) = listing.entries {
+                    for job in entries.into_iter().filter_map(|(_, job)| job) {
+                        listings.prune(job);
+                    }
+                }
+                continue;
+            }
+            let mut ready = vec![listing];
+            while let Some(listing) = ready.pop() {
+                let entries = match listing.entries {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        visit(Err(e));
+                        continue;
+                    }
+                };
+                for (res, job) in entries {
+                    let descend = match res {
+                        Ok(entry) if entry.depth() < self.min_depth => true,
+                        res => visit(res),
+                    };
+                    if self.is_stopped() {
+                        return;
+                    }
+                    match (descend, job) {
+                        (_, None) => {}
+                        (false, Some(job)) => listings.prune(job),
+                        (true, Some(job)) => match listings.waiting.remove(&job) {
+                            Some(listing) => ready.push(listing),
+                            None => {
+                                expected.insert(job);
+                            }
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    /// visits `entry` and what is below it in walk order
+    fn in_order(
+        &self,
+        entry: Entry,
+        job: Option<usize>,
+        listings: &mut Listings,
+        visit: &mut VisitFn,
+    ) {
+        if self.is_stopped() {
+            return;
+        }
+        let shown = entry.depth() >= self.min_depth;
+        let mut entry = Some(entry);
+        if !self.contents_first && shown && !visit(Ok(entry.take().unwrap())) {
+            if let Some(job) = job {
+                listings.prune(job);
+            }
+            return;
+        }
+        if let Some(job) = job {
+            match listings.wait(job) {
+                Err(e) => {
+                    visit(Err(e));
+                }
+                Ok(mut entries) => {
+                    if let Some(sort) = self.sort {
+                        // errors first
+                        entries.sort_by(|(a, _), (b, _)| match (a, b) {
+                            (Ok(a), Ok(b)) => sort.compare(a, b),
+                            (a, b) => a.is_ok().cmp(&b.is_ok()),
+                        });
+                    }
+                    for (res, job) in entries {
+                        match res {
+                            Ok(entry) => self.in_order(entry, job, listings, visit),
+                            Err(e) => {
+                                visit(Err(e));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if let (true, Some(entry)) = (shown, entry) {
+            visit(Ok(entry));
+        }
+    }
+}
+
+/// the main thread's end of a parallel walk
+struct Listings<'a> {
+    rx: Receiver<Listing>,
+    pool: &'a Pool,
+    /// listings that came before their turn
+    waiting: HashMap<usize, Listing>,
+}
+
+impl Listings<'_> {
+    fn wait(&mut self, id: usize) -> Entries {
+        loop {
+            if let Some(listing) = self.waiting.remove(&id) {
+                return listing.entries;
+            }
+            match self.rx.recv() {
+                Ok(listing) => {
+                    self.waiting.insert(listing.id, listing);
+                }
+                Err(_) => return Ok(vec![]),
+            }
+        }
+    }
+
+    fn prune(&mut self, id: usize) {
+        self.pool.pruned.lock().unwrap().insert(id);
+        if let Some(Listing {
+            entries: Ok(entries),
+            ..
+        }) = self.waiting.remove(&id)
+        {
+            for job in entries.into_iter().filter_map(|(_, job)| job) {
+                self.prune(job);
+            }
+        }
+    }
+
+    fn is_pruned(&mut self, id: usize) -> bool {
+        self.pool.pruned.lock().unwrap().contains(&id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Entry;
+    use super::{
+        glob, parse_format, parse_mode, replace_braces, symbolic_mode, Cmp, Expr, Piece, Visit,
+    };
+    use std::{ffi::OsStr, fs};
+    use walkdir::WalkDir;
+
+    fn entry(path: &str) -> Entry {
+        WalkDir::new(path)
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap()
+            .into()
+    }
+
+    fn parse(expr: &str) -> Result<Expr, String> {
+        let args: Vec<String> = expr.split_whitespace().map(String::from).collect();
+        Expr::parse(&args)
+            .map(|(expr, _)| expr)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_eval() {
+        let file = "tests/inputs/a/a.txt";
+        let dir = "tests/inputs/a/b";
+        let table = [
+            ("", file, true),
+            ("-true", file, true),
+            ("-false", file, false),
+            ("-name a", file, false),
+            ("-name a*", file, true),
+            ("-name *.TXT", file, false),
+            ("-iname *.TXT", file, true),
+            ("-name ?.txt", file, true),
+            ("-name [!b].txt", file, true),
+            ("-name b*", file, false),
+            ("-path tests/*/a.txt", file, true),
+            ("-path */A/*", file, false),
+            ("-ipath */A/*", file, true),
+            ("-regex .*/a/.*", file, true),
+            ("-regex a.txt", file, false),
+            ("-regex .*A.TXT", file, false),
+            ("-iregex .*A.TXT", file, true),
+            ("-type f", file, true),
+            ("-type d", file, false),
+            ("-type d", dir, true),
+            ("-type l,d", dir, true),
+            ("! -type f", file, false),
+            ("-not -not -type f", file, true),
+            ("-name a* -type d", file, false),
+            ("-name a* -a -type f", file, true),
+            ("-name a* -and -type d", file, false),
+            ("-name z -o -type f", file, true),
+            ("-name z -or -type d", file, false),
+            ("-false -o -false -o -true", file, true),
+            ("-true -o -false -a -false", file, true),
+            ("( -true -o -false ) -a -false", file, false),
+            ("! ( -name z -o -type d )", file, true),
+            ("( -name a* -a -type f ) -o ! -name b", dir, false),
+            ("( -name a* -a -type f ) -o ! -name b", file, true),
+            ("-size 2c", file, true),
+            ("-size -3c", file, true),
+            ("-size +1c", file, true),
+            ("-size 1", file, true),
+            ("-size 1k", file, true),
+            ("-size -1k", file, false),
+            ("-mtime -100000", file, true),
+            ("-mmin +0", file, true),
+            ("-atime -100000", file, true),
+            ("-ctime -100000", file, true),
+            ("-newer tests/inputs/a/a.txt", file, false),
+            ("-empty", file, false),
+            ("-empty", dir, false),
+            ("-contains ^\\s$", file, true),
+            ("-contains-fixed \\s", file, false),
+            ("-contains .*", dir, false),
+        ];
+        for (expr, path, expected) in table {
+            let parsed = parse(expr).unwrap();
+            let entry = entry(path);
+            assert_eq!(
+                parsed.eval(&Visit::new(&entry)),
+                expected,
+                "{} on {}",
+                expr,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let table = [
+            ("(", "invalid expression; expected an expression at the end"),
+            ("( -true", "invalid expression; missing ')'"),
+            ("-true )", "invalid expression; you have too many ')'"),
+            (")", "invalid expression; you have too many ')'"),
+            (
+                "-o -true",
+                "invalid expression; you have used a binary operator '-o' with nothing before it.",
+            ),
+            (
+                "-true -a",
+                "invalid expression; expected an expression at the end",
+            ),
+            ("!", "invalid expression; expected an expression at the end"),
+            ("-name", "missing argument to '-name'"),
+            ("-type x", "unknown argument to -type: x"),
+            ("-bogus", "unknown predicate '-bogus'"),
+            ("-size", "missing argument to '-size'"),
+            ("-size 1x", "invalid argument '1x' to '-size'"),
+            ("-size +", "invalid argument '+' to '-size'"),
+            ("-mtime 1d", "invalid argument '1d' to '-mtime'"),
+            ("-perm 9", "invalid mode '9'"),
+            ("-perm /u+z", "invalid mode '/u+z'"),
+            ("-links x", "invalid argument 'x' to '-links'"),
+            (
+                "-user no-such-user-here",
+                "-user: 'no-such-user-here' is not the name of a known user",
+            ),
+            (
+                "-group no-such-group-here",
+                "-group: 'no-such-group-here' is not the name of an existing group",
+            ),
+            ("-cmin", "missing argument to '-cmin'"),
+            ("-exec echo {}", "missing argument to '-exec'"),
+            ("-execdir ;", "missing argument to '-execdir'"),
+            ("-ok echo {} +", "missing argument to '-ok'"),
+            (
+                "-exec echo {} {} +",
+                "only one instance of {} is supported with -exec ... +",
+            ),
+        ];
+        for (expr, expected) in table {
+            assert_eq!(parse(expr).unwrap_err(), expected, "{}", expr);
+        }
+        assert!(parse("-regex [").is_err());
+        assert!(parse("-newer tests/inputs/no-such-file").is_err());
+    }
+
+    #[test]
+    fn test_implicit_print() {
+        let has_action = |expr| parse(expr).unwrap().any(&Expr::is_action);
+        assert!(!has_action("-name a -o ( -type f ! -empty )"));
+        assert!(has_action("-name a -o ( -type f ! -print )"));
+        assert!(has_action("-name a -exec echo {} ;"));
+        assert!(has_action("-name a -execdir echo {} + -o -delete"));
+    }
+
+    #[test]
+    fn test_contains_last() {
+        let first = |expr| match parse(expr).unwrap() {
+            Expr::And(left, _) => format!("{:?}", left),
+            other => panic!("not an and: {:?}", other),
+        };
+        assert!(first("-contains x -name a").starts_with("Name"));
+        assert!(first("( -contains x -o -contains y ) -size +1").starts_with("Size"));
+        assert!(first("-name a -contains x").starts_with("Name"));
+        // side effects keep their place
+        assert!(first("-contains x -print").starts_with("Contains"));
+        assert!(first("-contains x -prune").starts_with("Contains"));
+    }
+
+    #[test]
+    fn test_set_binary() {
+        let text = |e: &Expr| matches!(e, Expr::Contains(c) if !c.binary);
+        let mut expr = parse("-name a -o ! ( -contains x -a -contains-fixed y )").unwrap();
+        assert!(expr.any(&text));
+        expr.set_binary();
+        assert!(!expr.any(&text));
+    }
+
+    #[test]
+    fn test_replace_braces() {
+        let path = OsStr::new("a/b.txt");
+        assert_eq!(replace_braces("{}", path), "a/b.txt");
+        assert_eq!(replace_braces("x", path), "x");
+        assert_eq!(replace_braces("--file={}.bak", path), "--file=a/b.txt.bak");
+        assert_eq!(replace_braces("{}:{}", path), "a/b.txt:a/b.txt");
+    }
+
+    #[test]
+    fn test_glob() {
+        let table = [
+            ("*.rs", "lib.rs", true),
+            ("*.rs", "lib.rsx", false),
+            ("*.rs", ".hidden.rs", true),
+            ("*", "a/b", true),
+            ("?", "ab", false),
+            ("a?c", "abc", true),
+            ("[abc]x", "bx", true),
+            ("[!abc]x", "bx", false),
+            ("[^abc]x", "dx", true),
+            ("[a-c]", "b", true),
+            ("[a-c]", "-", false),
+            ("[a-]", "-", true),
+            ("[]]", "]", true),
+            ("[!]]", "a", true),
+            ("[[:digit:]]*", "1st", true),
+            ("[[:digit:]]*", "first", false),
+            ("[.$]", "$", true),
+            ("[", "[", true),
+            ("a[b", "a[b", true),
+            (r"\*", "*", true),
+            (r"\*", "a", false),
+            ("a.b", "axb", false),
+            ("(x)|+", "(x)|+", true),
+        ];
+        for (pattern, text, expected) in table {
+            let regex = glob(pattern, false).unwrap();
+            assert_eq!(regex.is_match(text), expected, "{} on {}", pattern, text);
+        }
+        assert!(glob("*.RS", true).unwrap().is_match("lib.rs"));
+        assert!(!glob("*.RS", false).unwrap().is_match("lib.rs"));
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        let table = [
+            ("644", Some(0o644)),
+            ("0755", Some(0o755)),
+            ("4755", Some(0o4755)),
+            ("17777", None),
+            ("u+w", Some(0o200)),
+            ("o+w", Some(0o002)),
+            ("+w", Some(0o222)),
+            ("a=rx", Some(0o555)),
+            ("u=rwx,go=rx", Some(0o755)),
+            ("u+s", Some(0o4000)),
+            ("g+s,o+t", Some(0o3000)),
+            ("ug+rw-w", Some(0o440)),
+            ("u+r,u-r", Some(0)),
+            ("", None),
+            ("x+w", None),
+            ("u+q", None),
+            ("u", None),
+            ("89", None),
+        ];
+        for (mode, expected) in table {
+            assert_eq!(parse_mode(mode), expected, "{}", mode);
+        }
+    }
+
+    #[test]
+    fn test_parse_format() {
+        let directive = |letter, field, width, left| Piece::Directive {
+            letter,
+            field,
+            width,
+            left,
+        };
+        assert_eq!(
+            parse_format(r"%p\n").unwrap(),
+            [directive('p', None, 0, false), Piece::Text(b"\n".to_vec())]
+        );
+        assert_eq!(
+            parse_format(r"100%% %-8u%12s\0").unwrap(),
+            [
+                Piece::Text(b"100% ".to_vec()),
+                directive('u', None, 8, true),
+                directive('s', None, 12, false),
+                Piece::Text(b"\0".to_vec()),
+            ]
+        );
+        assert_eq!(
+            parse_format("%TY-%Tm%AH\\x\\").unwrap(),
+            [
+                directive('T', Some('Y'), 0, false),
+                Piece::Text(b"-".to_vec()),
+                directive('T', Some('m'), 0, false),
+                directive('A', Some('H'), 0, false),
+                Piece::Text(b"\\x\\".to_vec()),
+            ]
+        );
+        assert!(parse_format("%q").is_err());
+        assert!(parse_format("%Tq").is_err());
+        assert!(parse_format("%").is_err());
+    }
+
+    #[test]
+    fn test_symbolic_mode() {
+        let dir = fs::metadata("tests/inputs").unwrap().file_type();
+        let file = fs::metadata("tests/inputs/g.csv").unwrap().file_type();
+        assert_eq!(symbolic_mode(dir, 0o755), "drwxr-xr-x");
+        assert_eq!(symbolic_mode(file, 0o640), "-rw-r-----");
+        assert_eq!(symbolic_mode(file, 0o4755), "-rwsr-xr-x");
+        assert_eq!(symbolic_mode(file, 0o2644), "-rw-r-Sr--");
+        assert_eq!(symbolic_mode(dir, 0o1777), "drwxrwxrwt");
+    }
+
+    #[test]
+    fn test_parse_cmp() {
+        assert_eq!(Cmp::parse("3"), Some((Cmp::Exactly(3), "")));
+        assert_eq!(Cmp::parse("+3k"), Some((Cmp::More(3), "k")));
+        assert_eq!(Cmp::parse("-10M"), Some((Cmp::Less(10), "M")));
+        assert_eq!(Cmp::parse("+"), None);
+        assert_eq!(Cmp::parse("k"), None);
+    }
+
+    #[test]
+    fn test_lazy_metadata() {
+        let entry = entry("tests/inputs/a/a.txt");
+        let table = [
+            ("-name a", false),
+            ("-false -a -size +0", false),
+            ("-type f -o -mtime 0", false),
+            ("-size +0", true),
+            ("-type d -o -newer tests/inputs/g.csv", true),
+        ];
+        for (expr, fetched) in table {
+            let visit = Visit::new(&entry);
+            parse(expr).unwrap().eval(&visit);
+            assert_eq!(visit.metadata.get().is_some(), fetched, "{}", expr);
+        }
+    }
 }
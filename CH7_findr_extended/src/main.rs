@@ -1,6 +1,18 @@
+use std::io;
+
 fn main() {
-    if let Err(e) = findr::get_args().and_then(findr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    match findr::get_args().and_then(findr::run) {
+        Err(e) => {
+            // like find killed by SIGPIPE, quietly stop once the reader is gone
+            let broken_pipe = e
+                .downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe);
+            if !broken_pipe {
+                eprintln!("{}", e);
+            }
+            std::process::exit(1);
+        }
+        Ok(false) => std::process::exit(1),
+        Ok(true) => (),
     }
 }
@@ -1,7 +1,13 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::{borrow::Cow, fs, path::Path};
+use std::{
+    borrow::Cow,
+    fs::{self, File, FileTimes},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tempfile::TempDir;
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -37,6 +43,7 @@ fn skips_bad_dir() -> TestResult {
 
 // --------------------------------------------------
 #[test]
+#[allow(clippy::needless_borrows_for_generic_args)]
 fn dies_bad_name() -> TestResult {
     Command::cargo_bin(PRG)?
         .args(&["--name", "*.csv"])
@@ -48,6 +55,7 @@ fn dies_bad_name() -> TestResult {
 
 // --------------------------------------------------
 #[test]
+#[allow(clippy::needless_borrows_for_generic_args)]
 fn dies_bad_type() -> TestResult {
     let expected = "error: invalid value 'x' for '--type <TYPE>...'";
     Command::cargo_bin(PRG)?
@@ -60,6 +68,7 @@ fn dies_bad_type() -> TestResult {
 
 // --------------------------------------------------
 #[cfg(windows)]
+#[allow(mismatched_lifetime_syntaxes)]
 fn format_file_name(expected_file: &str) -> Cow<str> {
     // Equivalent to: Cow::Owned(format!("{}.windows", expected_file))
     format!("{}.windows", expected_file).into()
@@ -67,6 +76,7 @@ fn format_file_name(expected_file: &str) -> Cow<str> {
 
 // --------------------------------------------------
 #[cfg(not(windows))]
+#[allow(mismatched_lifetime_syntaxes)]
 fn format_file_name(expected_file: &str) -> Cow<str> {
     // Equivalent to: Cow::Borrowed(expected_file)
     expected_file.into()
@@ -92,6 +102,23 @@ fn run(args: &[&str], expected_file: &str) -> TestResult {
     Ok(())
 }
 
+// --------------------------------------------------
+// like run, for the lines `cmd` prints in any order
+fn run_sorted(cmd: &mut Command, expected: &[&str]) -> TestResult {
+    let cmd = cmd.assert().success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut lines: Vec<String> = stdout
+        .split('\n')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.replace('\\', "/"))
+        .collect();
+    lines.sort();
+    let mut expected = expected.to_vec();
+    expected.sort();
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn path1() -> TestResult {
@@ -284,7 +311,14 @@ fn path_g() -> TestResult {
 // --------------------------------------------------
 #[test]
 #[cfg(not(windows))]
+#[allow(clippy::needless_borrows_for_generic_args)]
 fn unreadable_dir() -> TestResult {
+    // root reads the directory anyway
+    #[cfg(unix)]
+    if unsafe { libc::geteuid() } == 0 {
+        return Ok(());
+    }
+
     let dirname = "tests/inputs/cant-touch-this";
     if !Path::new(dirname).exists() {
         fs::create_dir(dirname)?;
@@ -316,3 +350,1467 @@ fn unreadable_dir() -> TestResult {
     assert!(stderr.contains("cant-touch-this: Permission denied"));
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn expr_name_type_or_not_name() -> TestResult {
+    run(
+        &[
+            "tests/inputs",
+            "(",
+            "-name",
+            "*a*",
+            "-a",
+            "-type",
+            "f",
+            ")",
+            "-o",
+            "!",
+            "-name",
+            "*.*",
+        ],
+        "tests/expected/expr_name_type_or_not_name.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_type_d_not_name() -> TestResult {
+    run(
+        &["tests/inputs", "-type", "d", "-not", "-name", "[a-c]"],
+        "tests/expected/expr_type_d_not_name.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_type_flag_and_or() -> TestResult {
+    run(
+        &["tests/inputs", "-t", "f", "-name", "*csv*", "-o", "-name", "*mp3*"],
+        "tests/expected/expr_type_flag_and_or.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_type_list() -> TestResult {
+    run(
+        &["tests/inputs", "-type", "f,l", "-name", "*.csv"],
+        "tests/expected/expr_type_list.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_options_after() -> TestResult {
+    run(
+        &["tests/inputs", "-name", "*csv*", "-o", "-name", "*mp3*", "-t", "f"],
+        "tests/expected/expr_type_flag_and_or.txt",
+    )?;
+    let before = Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--count", "-name", "a.txt"])
+        .output()?;
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-name", "a.txt", "--count"])
+        .assert()
+        .success()
+        .stdout(before.stdout);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_word_as_option_value() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    fs::write(dir.path().join("a!b"), "")?;
+    fs::write(dir.path().join("c"), "")?;
+    let root = dir.path().to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args([root, "-n", "!", "-type", "f"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", dir.path().join("a!b").display()));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn dies_non_utf8_argument() -> TestResult {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-name"])
+        .arg(OsStr::from_bytes(b"caf\xe9"))
+        .assert()
+        .failure()
+        .code(1)
+        .stderr("invalid UTF-8 argument: \"caf\\xE9\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_expr() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "(", "-name", "a"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid expression; missing ')'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_expr_type() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-type", "x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown argument to -type: x"));
+    Ok(())
+}
+
+// --------------------------------------------------
+const DAY: u64 = 24 * 60 * 60;
+
+// --------------------------------------------------
+fn touch(path: &Path, len: u64, modified_days_ago: u64) -> TestResult {
+    let now = SystemTime::now();
+    let file = File::create(path)?;
+    file.set_len(len)?;
+    file.set_times(
+        FileTimes::new()
+            .set_modified(now - Duration::from_secs(modified_days_ago * DAY))
+            .set_accessed(now - Duration::from_secs(3 * DAY)),
+    )?;
+    Ok(())
+}
+
+// --------------------------------------------------
+fn metadata_tree() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    fs::create_dir(root.join("empty_dir"))?;
+    fs::create_dir(root.join("full_dir"))?;
+    touch(&root.join("full_dir/empty.txt"), 0, 0)?;
+    touch(&root.join("small.txt"), 100, 0)?;
+    touch(&root.join("medium.txt"), 2000, 1)?;
+    touch(&root.join("big.bin"), 2 * 1024 * 1024, 5)?;
+    touch(&root.join("old.txt"), 10, 10)?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+fn run_tree(root: &Path, args: &[&str], expected: &[&str]) -> TestResult {
+    run_sorted(
+        Command::cargo_bin(PRG)?.current_dir(root).arg(".").args(args),
+        expected,
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn size_units() -> TestResult {
+    let dir = metadata_tree()?;
+    let root = dir.path();
+    run_tree(
+        root,
+        &["-type", "f", "-size", "-1"],
+        &["./full_dir/empty.txt"],
+    )?;
+    run_tree(root, &["-size", "100c"], &["./small.txt"])?;
+    run_tree(root, &["-type", "f", "-size", "4"], &["./medium.txt"])?;
+    run_tree(root, &["-type", "f", "-size", "2k"], &["./medium.txt"])?;
+    run_tree(root, &["-size", "+1M"], &["./big.bin"])?;
+    run_tree(
+        root,
+        &["-type", "f", "-size", "+1k", "-size", "-2M"],
+        &["./medium.txt"],
+    )?;
+    run_tree(
+        root,
+        &["-type", "f", "-size", "1"],
+        &["./small.txt", "./old.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mtime_days() -> TestResult {
+    let dir = metadata_tree()?;
+    let root = dir.path();
+    run_tree(root, &["-mtime", "+7"], &["./old.txt"])?;
+    run_tree(root, &["-mtime", "5"], &["./big.bin"])?;
+    run_tree(
+        root,
+        &["-type", "f", "-mtime", "-2"],
+        &["./full_dir/empty.txt", "./small.txt", "./medium.txt"],
+    )?;
+    run_tree(
+        root,
+        &["-mmin", "+2000", "-mmin", "-2000000"],
+        &["./big.bin", "./old.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn atime_ctime() -> TestResult {
+    let dir = metadata_tree()?;
+    let root = dir.path();
+    run_tree(
+        root,
+        &["-type", "f", "-atime", "3"],
+        &[
+            "./full_dir/empty.txt",
+            "./small.txt",
+            "./medium.txt",
+            "./big.bin",
+            "./old.txt",
+        ],
+    )?;
+    run_tree(root, &["-type", "f", "-cmin", "+60"], &[])?;
+    run_tree(root, &["-name", "old*", "-ctime", "0"], &["./old.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+fn newer() -> TestResult {
+    let dir = metadata_tree()?;
+    run_tree(
+        dir.path(),
+        &["-type", "f", "-newer", "big.bin"],
+        &["./full_dir/empty.txt", "./small.txt", "./medium.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn empty() -> TestResult {
+    let dir = metadata_tree()?;
+    run_tree(
+        dir.path(),
+        &["-empty"],
+        &["./empty_dir", "./full_dir/empty.txt"],
+    )?;
+    run_tree(
+        dir.path(),
+        &["-type", "d", "!", "-empty"],
+        &[".", "./full_dir"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_size() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-size", "10x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid argument '10x' to '-size'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_newer() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = format!("{}: .* [(]os error [23][)]", &bad);
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-newer", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+fn exec_tree() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    fs::create_dir_all(root.join("a/b"))?;
+    fs::write(root.join("a/x.txt"), "in a\n")?;
+    fs::write(root.join("a/b/y.txt"), "in b\n")?;
+    fs::write(root.join("z.log"), "at the top\n")?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_each() -> TestResult {
+    let dir = exec_tree()?;
+    run_tree(
+        dir.path(),
+        &["-name", "*.txt", "-exec", "echo", "file:{}", ";"],
+        &["file:./a/b/y.txt", "file:./a/x.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_command_braces() -> TestResult {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = exec_tree()?;
+    let script = dir.path().join("a/run.sh");
+    fs::write(&script, "#!/bin/sh\necho ran $0 $1\n")?;
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+    run_tree(
+        dir.path(),
+        &["-name", "run.sh", "-exec", "{}", "{}", ";"],
+        &["ran ./a/run.sh ./a/run.sh"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_as_test() -> TestResult {
+    let dir = exec_tree()?;
+    run_tree(
+        dir.path(),
+        &["-exec", "grep", "-q", "in", "{}", ";", "-print"],
+        &["./a/b/y.txt", "./a/x.txt"],
+    )?;
+    run_tree(dir.path(), &["-type", "f", "-exec", "false", ";"], &[])
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_batch() -> TestResult {
+    let dir = exec_tree()?;
+    run_tree(
+        dir.path(),
+        &["-type", "f", "-exec", "sh", "-c", "echo $#", "sh", "{}", "+"],
+        &["3"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_batch_failure() -> TestResult {
+    let dir = exec_tree()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args([".", "-type", "f", "-exec", "false", "{}", "+"])
+        .assert()
+        .failure()
+        .code(1);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn execdir() -> TestResult {
+    let dir = exec_tree()?;
+    run_tree(
+        dir.path(),
+        &["-name", "*.txt", "-execdir", "cat", "{}", ";", "-print"],
+        &["./a/b/y.txt", "./a/x.txt", "in a", "in b"],
+    )?;
+    run_tree(
+        dir.path(),
+        &["-type", "f", "-execdir", "echo", "{}", "+"],
+        &["./x.txt", "./y.txt", "./z.log"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn ok_asks() -> TestResult {
+    let dir = exec_tree()?;
+    for (answer, expected) in [("y\n", "run ./z.log\n"), ("no\n", "")] {
+        Command::cargo_bin(PRG)?
+            .current_dir(dir.path())
+            .args([".", "-name", "*.log", "-ok", "echo", "run", "{}", ";"])
+            .write_stdin(answer)
+            .assert()
+            .success()
+            .stdout(expected)
+            .stderr("< echo ... ./z.log > ? ");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete() -> TestResult {
+    let dir = exec_tree()?;
+    run_tree(dir.path(), &["-name", "*.txt", "-delete"], &[])?;
+    run_tree(dir.path(), &[], &[".", "./a", "./a/b", "./z.log"])?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["a", "-delete"])
+        .assert()
+        .success()
+        .stdout("");
+    run_tree(dir.path(), &[], &[".", "./z.log"])
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_failure() -> TestResult {
+    let dir = exec_tree()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args([".", "-name", "a", "-delete"])
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains("a: "));
+    assert!(dir.path().join("a/x.txt").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_exec() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-exec", "echo", "{}"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("missing argument to '-exec'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn printf_depth_type_name() -> TestResult {
+    run(
+        &["tests/inputs", "-printf", r"%d %y %f\n"],
+        "tests/expected/printf_depth_type_name.txt",
+    )
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+fn printf_tree() -> Result<TempDir, Box<dyn std::error::Error>> {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    fs::create_dir(root.join("sub"))?;
+    let data = root.join("sub/data.txt");
+    fs::write(&data, "hello")?;
+    fs::set_permissions(&data, fs::Permissions::from_mode(0o640))?;
+    // 2021-03-04 05:06:07 UTC
+    File::options().write(true).open(&data)?.set_modified(
+        UNIX_EPOCH + Duration::from_secs(1_614_834_367),
+    )?;
+    symlink("sub/data.txt", root.join("link"))?;
+    fs::write(root.join("new\nline"), "")?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+fn run_printf(root: &Path, args: &[&str], expected: &[&str]) -> TestResult {
+    run_sorted(
+        Command::cargo_bin(PRG)?
+            .current_dir(root)
+            .env("TZ", "UTC")
+            .arg(".")
+            .args(args),
+        expected,
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn printf_fields() -> TestResult {
+    let dir = printf_tree()?;
+    run_printf(
+        dir.path(),
+        &["-name", "data*", "-printf", r"%p|%f|%h|%s|%d|%y|%m|%M\n"],
+        &["./sub/data.txt|data.txt|./sub|5|2|f|640|-rw-r-----"],
+    )?;
+    run_printf(
+        dir.path(),
+        &["-name", "link", "-printf", r"%y %l [%-6f] [%6f]\n"],
+        &["l sub/data.txt [link  ] [  link]"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn printf_times() -> TestResult {
+    let dir = printf_tree()?;
+    run_printf(
+        dir.path(),
+        &["-name", "data*", "-printf", r"%TY-%Tm-%Td %TH:%TM|%T+|%T@\n%t\n"],
+        &[
+            "2021-03-04 05:06|2021-03-04+05:06:07.0000000000|1614834367.0000000000",
+            "Thu Mar  4 05:06:07.0000000000 2021",
+        ],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn printf_owner() -> TestResult {
+    let dir = printf_tree()?;
+    let cmd = Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args([".", "-name", "data*", "-printf", r"%u %g %i %n"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let fields: Vec<&str> = stdout.split(' ').collect();
+    assert_eq!(fields.len(), 4);
+    assert!(fields.iter().all(|f| !f.is_empty()));
+    assert!(fields[2].parse::<u64>().is_ok());
+    assert_eq!(fields[3], "1");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn print0() -> TestResult {
+    let dir = printf_tree()?;
+    let cmd = Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args([".", "-type", "f", "-print0"])
+        .assert()
+        .success();
+    let stdout = &cmd.get_output().stdout;
+    assert_eq!(stdout.last(), Some(&0));
+    let mut paths: Vec<&[u8]> = stdout[..stdout.len() - 1].split(|b| *b == 0).collect();
+    paths.sort();
+    assert_eq!(paths, [&b"./new\nline"[..], b"./sub/data.txt"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn print0_non_utf8() -> TestResult {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    let dir = tempfile::tempdir()?;
+    let name = OsStr::from_bytes(b"caf\xe9");
+    if File::create(dir.path().join(name)).is_err() {
+        // the file system only takes UTF-8 names
+        return Ok(());
+    }
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args([".", "-type", "f", "-print0"])
+        .assert()
+        .success()
+        .stdout(&b"./caf\xe9\0"[..]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn ls() -> TestResult {
+    let dir = printf_tree()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .env("TZ", "UTC")
+        .args([".", "(", "-name", "data*", "-o", "-name", "link", ")", "-ls"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(
+            r"(?m)^ +\d+ +\d+ -rw-r----- +1 \S+ +\S+ +5 Mar  4  2021 \./sub/data\.txt$",
+        )?)
+        .stdout(predicate::str::is_match(
+            r"(?m)^ +\d+ +0 lrwxrwxrwx +1 \S+ +\S+ +12 \w{3} [ \d]\d [ \d]\d:\d\d \./link -> sub/data\.txt$",
+        )?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_printf() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-printf", "%z"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid directive '%z' in -printf format",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+fn walk_tree() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    for dir in ["src/.git/objects", "target/debug", "real/sub"] {
+        fs::create_dir_all(root.join(dir))?;
+    }
+    for file in ["src/a.rs", "src/.git/HEAD", "target/debug/x", "real/sub/f"] {
+        File::create(root.join(file))?;
+    }
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+fn prune() -> TestResult {
+    let dir = walk_tree()?;
+    run_tree(
+        dir.path(),
+        &["(", "-name", ".git", "-o", "-name", "target", ")", "-prune", "-o", "-type", "f", "-print"],
+        &["./real/sub/f", "./src/a.rs"],
+    )?;
+    // a pruned directory still matches
+    run_tree(
+        dir.path(),
+        &["(", "-name", ".git", "-o", "-name", "target", ")", "-prune"],
+        &["./src/.git", "./target"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn depth() -> TestResult {
+    let dir = walk_tree()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["real", "-depth"])
+        .assert()
+        .success()
+        .stdout("real/sub/f\nreal/sub\nreal\n");
+    // -prune does nothing once the contents came first
+    run_tree(
+        dir.path(),
+        &["-depth", "-name", "target", "-prune", "-o", "-type", "f", "-print"],
+        &["./real/sub/f", "./src/.git/HEAD", "./src/a.rs", "./target/debug/x"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_links() -> TestResult {
+    let dir = walk_tree()?;
+    let root = dir.path();
+    std::os::unix::fs::symlink("real", root.join("link"))?;
+    run_tree(root, &["-name", "f"], &["./real/sub/f"])?;
+    run_tree(root, &["-H", "-name", "f"], &["./real/sub/f"])?;
+    run_tree(root, &["-L", "-name", "f"], &["./link/sub/f", "./real/sub/f"])?;
+    for (args, expected) in [
+        (&["link"][..], "link\n"),
+        (&["-H", "link"][..], "link\nlink/sub\nlink/sub/f\n"),
+        (&["-L", "link"][..], "link\nlink/sub\nlink/sub/f\n"),
+        (&["-L", "link", "-type", "l"][..], ""),
+    ] {
+        Command::cargo_bin(PRG)?
+            .current_dir(root)
+            .args(args)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_links_loop() -> TestResult {
+    let dir = walk_tree()?;
+    std::os::unix::fs::symlink("..", dir.path().join("real/sub/up"))?;
+    run_tree(
+        dir.path(),
+        &["-path", "./real*"],
+        &["./real", "./real/sub", "./real/sub/f", "./real/sub/up"],
+    )?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-L", "real", "-type", "f"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("real/sub/f\n")
+        .stderr(predicate::str::contains(
+            "File system loop found: real/sub/up points to an ancestor real",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn xdev() -> TestResult {
+    let dir = walk_tree()?;
+    for flag in ["-xdev", "-mount"] {
+        run_tree(
+            dir.path(),
+            &["-type", "f", flag],
+            &["./real/sub/f", "./src/.git/HEAD", "./src/a.rs", "./target/debug/x"],
+        )?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(target_os = "linux")]
+fn xdev_mount_point() -> TestResult {
+    use std::os::unix::fs::MetadataExt;
+    // /dev/pts is its own file system wherever a terminal can be opened
+    let (Ok(dev), Ok(pts)) = (fs::metadata("/dev"), fs::metadata("/dev/pts")) else {
+        return Ok(());
+    };
+    if dev.dev() == pts.dev() {
+        return Ok(());
+    }
+    let output = |args: &[&str]| -> Result<String, Box<dyn std::error::Error>> {
+        let cmd = Command::cargo_bin(PRG)?.args(args).assert();
+        Ok(String::from_utf8(cmd.get_output().stdout.clone())?)
+    };
+    let crossed = output(&["/dev/", "--max-depth", "2", "-name", "ptmx"])?;
+    let stayed = output(&["/dev/", "--max-depth", "2", "-xdev", "-name", "ptmx"])?;
+    assert!(crossed.contains("/dev/pts/ptmx"));
+    assert!(!stayed.contains("/dev/pts/ptmx"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn iname_csv() -> TestResult {
+    run(
+        &["tests/inputs", "-iname", "*.CSV"],
+        "tests/expected/iname_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn path_glob() -> TestResult {
+    run(
+        &["tests/inputs", "-path", "*/a/*"],
+        "tests/expected/path_glob_a.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn regex_whole_path() -> TestResult {
+    run(
+        &["tests/inputs", "-regex", ".*/[a-c]"],
+        "tests/expected/regex_single_letter.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_flag_still_partial() -> TestResult {
+    run(
+        &["tests/inputs", "--name", "csv"],
+        "tests/expected/name_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+fn perm_tree() -> Result<TempDir, Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempfile::tempdir()?;
+    for (name, mode) in [
+        ("open", 0o777),
+        ("setuid", 0o4755),
+        ("shared", 0o664),
+        ("plain", 0o644),
+        ("private", 0o600),
+    ] {
+        let path = dir.path().join(name);
+        File::create(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+    }
+    fs::hard_link(dir.path().join("plain"), dir.path().join("plain2"))?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn perm() -> TestResult {
+    let dir = perm_tree()?;
+    let root = dir.path();
+    let files = ["-type", "f", "-perm"];
+    for (mode, expected) in [
+        ("644", &["./plain", "./plain2"][..]),
+        ("u=rw,go=r", &["./plain", "./plain2"]),
+        ("-002", &["./open"]),
+        ("-o+w", &["./open"]),
+        ("-g+w", &["./open", "./shared"]),
+        ("/4000", &["./setuid"]),
+        ("/u+s,g+s", &["./setuid"]),
+        ("/022", &["./open", "./shared"]),
+        ("-0", &["./open", "./plain", "./plain2", "./private", "./setuid", "./shared"]),
+    ] {
+        run_tree(root, &[&files[..], &[mode]].concat(), expected)?;
+    }
+    run_tree(root, &["-type", "f", "!", "-perm", "/044"], &["./private"])
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn owner() -> TestResult {
+    use std::os::unix::fs::MetadataExt;
+    let dir = perm_tree()?;
+    let root = dir.path();
+    let meta = fs::metadata(root.join("plain"))?;
+    let (uid, gid) = (meta.uid().to_string(), meta.gid().to_string());
+    let all = ["./open", "./plain", "./plain2", "./private", "./setuid", "./shared"];
+    run_tree(root, &["-type", "f", "-uid", &uid], &all)?;
+    run_tree(root, &["-type", "f", "-gid", &gid], &all)?;
+    run_tree(root, &["-type", "f", "-user", &uid, "-group", &gid], &all)?;
+    run_tree(root, &["-type", "f", "-uid", &format!("+{}", uid)], &[])?;
+    run_tree(root, &["-type", "f", "-nouser"], &[])?;
+    run_tree(root, &["-type", "f", "-nogroup"], &[])
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn nouser() -> TestResult {
+    let dir = perm_tree()?;
+    // only root can give a file away
+    if std::os::unix::fs::chown(dir.path().join("private"), Some(54321), Some(54321)).is_err() {
+        return Ok(());
+    }
+    run_tree(dir.path(), &["-nouser"], &["./private"])?;
+    run_tree(dir.path(), &["-nogroup", "-uid", "54321"], &["./private"])
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn inode_links() -> TestResult {
+    use std::os::unix::fs::MetadataExt;
+    let dir = perm_tree()?;
+    let root = dir.path();
+    let ino = fs::metadata(root.join("plain"))?.ino().to_string();
+    run_tree(root, &["-inum", &ino], &["./plain", "./plain2"])?;
+    run_tree(root, &["-type", "f", "-links", "2"], &["./plain", "./plain2"])?;
+    run_tree(
+        root,
+        &["-type", "f", "-links", "-2"],
+        &["./open", "./private", "./setuid", "./shared"],
+    )?;
+    Command::cargo_bin(PRG)?
+        .current_dir(root)
+        .args([".", "-samefile", "plain2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("./plain\n"))
+        .stdout(predicate::str::contains("./plain2\n"))
+        .stdout(predicate::str::contains("./open").not());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_perm() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-perm", "u+q"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid mode 'u+q'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs() -> TestResult {
+    for (args, expected_file) in [
+        (&["tests/inputs"][..], "tests/expected/path1.txt"),
+        (&["tests/inputs/a/b", "tests/inputs/d"], "tests/expected/path_a_b_d.txt"),
+        (&["tests/inputs", "-t", "f"], "tests/expected/type_f.txt"),
+        (&["tests/inputs", "-t", "l"], "tests/expected/type_l.txt"),
+        (&["tests/inputs", "-name", "*.csv", "-o", "-name", "*.mp3"], "tests/expected/name_csv_mp3.txt"),
+        (&["tests/inputs", "-type", "d", "-not", "-name", "[a-c]"], "tests/expected/expr_type_d_not_name.txt"),
+    ] {
+        run(&[&["-j", "4"], args].concat(), expected_file)?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_depth_limits() -> TestResult {
+    let sequential = |args: &[&str]| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let cmd = Command::cargo_bin(PRG)?.args(args).assert().success();
+        Ok(cmd.get_output().stdout.clone())
+    };
+    for limits in [&["--max-depth", "2"][..], &["--min-depth", "2"], &["--max-depth", "0"]] {
+        let args = [&["--sort", "tests/inputs"], limits].concat();
+        let expected = sequential(&args)?;
+        Command::cargo_bin(PRG)?
+            .args([&["-j", "3"], &args[..]].concat())
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_sorted() -> TestResult {
+    let expected = Command::cargo_bin(PRG)?
+        .args(["--sort", "tests/inputs"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert!(expected.starts_with(b"tests/inputs\ntests/inputs/a\ntests/inputs/a/a.txt\n"));
+    for _ in 0..5 {
+        Command::cargo_bin(PRG)?
+            .args(["-j", "4", "--sort", "tests/inputs"])
+            .assert()
+            .success()
+            .stdout(expected.clone());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_prune_depth() -> TestResult {
+    let dir = walk_tree()?;
+    for jobs in ["1", "4"] {
+        let jobs = ["-j", jobs, "--sort"];
+        run_tree(
+            dir.path(),
+            &[
+                &jobs[..],
+                &["(", "-name", ".git", "-o", "-name", "target", ")", "-prune", "-o", "-type", "f", "-print"],
+            ]
+            .concat(),
+            &["./real/sub/f", "./src/a.rs"],
+        )?;
+        Command::cargo_bin(PRG)?
+            .current_dir(dir.path())
+            .args([&jobs[..], &["real", "src", "-depth"]].concat())
+            .assert()
+            .success()
+            .stdout("real/sub/f\nreal/sub\nreal\nsrc/.git/HEAD\nsrc/.git/objects\nsrc/.git\nsrc/a.rs\nsrc\n");
+        Command::cargo_bin(PRG)?
+            .current_dir(dir.path())
+            .args([&jobs[..], &["src", "-name", ".git", "-prune"]].concat())
+            .assert()
+            .success()
+            .stdout("src/.git\n");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-j", "4", "-c", "tests/inputs"])
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with(
+            "--------- tests/inputs ---------\n7 directories\n8 files\n1 links\n16 bytes\n\
+             depth entries\n    0 1\n    1 4\n    2 7\n    3 3\n    4 1\n",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn jobs_follow_links_loop() -> TestResult {
+    let dir = walk_tree()?;
+    std::os::unix::fs::symlink("..", dir.path().join("real/sub/up"))?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-L", "-j", "2", "real", "-type", "f"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("real/sub/f\n")
+        .stderr(predicate::str::contains(
+            "File system loop found: real/sub/up points to an ancestor real",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_jobs() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-j", "0", "tests/inputs"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value '0' for '--jobs <N>'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+// tests/repo is a git repository with every leading dot spelled "dot-", so
+// it does not get mixed up with the repository holding it
+fn repo_tree() -> Result<TempDir, Box<dyn std::error::Error>> {
+    fn copy(from: &Path, to: &Path) -> std::io::Result<()> {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let target = to.join(match name.strip_prefix("dot-") {
+                Some(rest) => format!(".{rest}"),
+                None => name,
+            });
+            if entry.file_type()?.is_dir() {
+                copy(&entry.path(), &target)?;
+            } else {
+                fs::copy(entry.path(), target)?;
+            }
+        }
+        Ok(())
+    }
+
+    let dir = tempfile::tempdir()?;
+    copy(Path::new("tests/repo"), &dir.path().join("repo"))?;
+    fs::create_dir_all(dir.path().join("home/.config"))?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+// runs in the repository with an empty home, so no global excludes apply
+// unless a test adds them
+fn run_repo(dir: &TempDir, args: &[&str], expected: &[&str]) -> TestResult {
+    let home = dir.path().join("home");
+    run_sorted(
+        Command::cargo_bin(PRG)?
+            .current_dir(dir.path().join("repo"))
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .args(args),
+        expected,
+    )
+}
+
+// what `git ls-files -o --exclude-standard` lists, less what .ignore has
+const REPO_FILES: &[&str] = &[
+    "./.gitignore",
+    "./.hidden/notes.md",
+    "./.ignore",
+    "./README.md",
+    "./docs/index.md",
+    "./keep.log",
+    "./notes.bak",
+    "./src/.gitignore",
+    "./src/build",
+    "./src/debug.log",
+    "./src/keep.log",
+    "./src/main.rs",
+    "./src/root-only.txt",
+];
+
+// --------------------------------------------------
+#[test]
+fn gitignore() -> TestResult {
+    let dir = repo_tree()?;
+    run_repo(&dir, &[".", "--gitignore", "-type", "f"], REPO_FILES)?;
+    run_repo(&dir, &[".", "--gitignore", "-type", "d"], &[".", "./.hidden", "./docs", "./src"])?;
+    // without it, nothing is left out
+    run_repo(
+        &dir,
+        &[".", "-name", "*.js", "-o", "-name", "exclude", "-o", "-name", "secret.txt"],
+        &["./.git/info/exclude", "./node_modules/pkg/index.js", "./secret.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn gitignore_jobs() -> TestResult {
+    let dir = repo_tree()?;
+    run_repo(&dir, &[".", "--gitignore", "-j", "3", "-type", "f"], REPO_FILES)
+}
+
+// --------------------------------------------------
+#[test]
+fn gitignore_below_top() -> TestResult {
+    // the ignore files above the starting point still apply
+    let dir = repo_tree()?;
+    run_repo(
+        &dir,
+        &["src", "--gitignore", "-name", "*.*"],
+        &["src/.gitignore", "src/debug.log", "src/keep.log", "src/main.rs", "src/root-only.txt"],
+    )?;
+    run_repo(&dir, &["docs", "--gitignore"], &["docs", "docs/index.md"])
+}
+
+// --------------------------------------------------
+#[test]
+fn gitignore_global() -> TestResult {
+    let dir = repo_tree()?;
+    let git = dir.path().join("home/.config/git");
+    fs::create_dir_all(&git)?;
+    fs::write(git.join("ignore"), "*.bak\n")?;
+    let expected: Vec<_> = REPO_FILES
+        .iter()
+        .copied()
+        .filter(|file| *file != "./notes.bak")
+        .collect();
+    run_repo(&dir, &[".", "--gitignore", "-type", "f"], &expected)
+}
+
+// --------------------------------------------------
+#[test]
+fn no_hidden() -> TestResult {
+    let dir = repo_tree()?;
+    run_repo(
+        &dir,
+        &[".", "--no-hidden", "--max-depth", "1"],
+        &[
+            ".",
+            "./README.md",
+            "./app.log",
+            "./docs",
+            "./keep.log",
+            "./local.tmp",
+            "./node_modules",
+            "./notes.bak",
+            "./root-only.txt",
+            "./secret.txt",
+            "./src",
+        ],
+    )?;
+    let visible: Vec<_> = REPO_FILES
+        .iter()
+        .copied()
+        .filter(|file| !file.contains("/."))
+        .collect();
+    run_repo(&dir, &[".", "--gitignore", "--no-hidden", "-type", "f"], &visible)?;
+    run_repo(&dir, &[".", "--gitignore", "--no-hidden", "-j", "2", "-type", "f"], &visible)?;
+    // the last one given wins
+    run_repo(&dir, &[".", "--gitignore", "--no-hidden", "--hidden", "-type", "f"], REPO_FILES)
+}
+
+// --------------------------------------------------
+#[test]
+fn count_per_path() -> TestResult {
+    // the report comes after all the paths, counted apart and then together
+    Command::cargo_bin(PRG)?
+        .args(["-c", "tests/inputs/a", "tests/inputs/d", "-name", "*.*"])
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with(
+            "tests/inputs/d/d.txt\n\
+             --------- tests/inputs/a ---------\n3 files\n6 bytes\n\
+             depth entries\n    0 0\n    1 1\n    2 1\n    3 1\n\
+             --------- tests/inputs/d ---------\n3 files\n1 links\n6 bytes\n\
+             depth entries\n    0 0\n    1 3\n    2 1\n\
+             --------- total ---------\n6 files\n1 links\n12 bytes\n\
+             depth entries\n    0 0\n    1 4\n    2 2\n    3 1\n",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count_json() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["-c", "--count-format", "json", "tests/inputs/a", "tests/inputs/d"])
+        .args(["-type", "d", "-o", "-type", "f"])
+        .assert()
+        .success();
+    let report: serde_json::Value = serde_json::from_slice(&cmd.get_output().stdout)?;
+    let counts = |dirs: u64, files: u64, depths: &[u64]| {
+        serde_json::json!({
+            "directories": dirs,
+            "files": files,
+            "links": 0,
+            "fifos": 0,
+            "sockets": 0,
+            "block_devices": 0,
+            "char_devices": 0,
+            "bytes": 6,
+            "depths": depths,
+        })
+    };
+    let mut a = counts(3, 3, &[1, 2, 2, 1]);
+    a["path"] = "tests/inputs/a".into();
+    let mut d = counts(2, 3, &[1, 3, 1]);
+    d["path"] = "tests/inputs/d".into();
+    let mut total = counts(5, 6, &[2, 5, 3, 1]);
+    total["bytes"] = 12.into();
+    assert_eq!(report, serde_json::json!({ "paths": [a, d], "total": total }));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn count_special_types() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let _socket = std::os::unix::net::UnixListener::bind(dir.path().join("sock"))?;
+    let status = std::process::Command::new("mkfifo")
+        .arg(dir.path().join("fifo"))
+        .status()?;
+    assert!(status.success());
+    fs::write(dir.path().join("file"), "hello")?;
+    run_tree(dir.path(), &["-type", "p"], &["./fifo"])?;
+    run_tree(dir.path(), &["-type", "s,f"], &["./file", "./sock"])?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-c", "--type", "p", "s", "f"])
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with(
+            "--------- . ---------\n1 files\n1 fifos\n1 sockets\n5 bytes\n\
+             depth entries\n    0 0\n    1 3\n",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_count_format_without_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--count-format", "json", "tests/inputs"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--count"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn closed_stdout() -> TestResult {
+    // as in findr -c tests/inputs | head -1, with the reader gone before
+    for args in [&["tests/inputs", "-c"][..], &["tests/inputs", "-j", "2"]] {
+        let (reader, writer) = std::io::pipe()?;
+        drop(reader);
+        let out = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+            .args(args)
+            .stdout(writer)
+            .output()?;
+        assert_eq!(out.status.code(), Some(1));
+        assert_eq!(String::from_utf8(out.stderr)?, "");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+fn contains_tree() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    fs::create_dir_all(root.join("src/unsafe"))?;
+    fs::write(root.join("src/a.rs"), "fn main() {\n    unsafe { f() }\n}\n")?;
+    fs::write(root.join("src/b.rs"), "// no unsafe here?\r\nfn b() {}\r\n")?;
+    fs::write(root.join("src/c.rs"), "fn c() {}\n")?;
+    fs::write(root.join("src/notes.txt"), "unsafe\n")?;
+    fs::write(root.join("src/lib.so"), b"\x7fELF\0\0unsafe\n")?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+fn contains() -> TestResult {
+    let dir = contains_tree()?;
+    run_tree(
+        dir.path(),
+        &["-name", "*.rs", "-contains", r"^\s*unsafe"],
+        &["./src/a.rs"],
+    )?;
+    // the line ending is not part of the line
+    run_tree(dir.path(), &["-contains", r"\?$"], &["./src/b.rs"])?;
+    run_tree(
+        dir.path(),
+        &["-contains", "unsafe"],
+        &["./src/a.rs", "./src/b.rs", "./src/notes.txt"],
+    )?;
+    run_tree(
+        dir.path(),
+        &["-contains", "unsafe", "-name", "*.rs", "!", "-contains", "no"],
+        &["./src/a.rs"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn contains_fixed() -> TestResult {
+    let dir = contains_tree()?;
+    run_tree(dir.path(), &["-contains-fixed", "f()"], &["./src/a.rs"])?;
+    run_tree(dir.path(), &["-contains-fixed", "here?"], &["./src/b.rs"])
+}
+
+// --------------------------------------------------
+#[test]
+fn contains_binary() -> TestResult {
+    let dir = contains_tree()?;
+    run_tree(dir.path(), &["-name", "*.so", "-contains", "unsafe"], &[])?;
+    run_tree(
+        dir.path(),
+        &["--binary", "-name", "*.so", "-contains", "unsafe"],
+        &["./src/lib.so"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_contains() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-contains", "("])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("regex parse error"));
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-contains-fixed"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("missing argument to '-contains-fixed'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+fn sort_tree() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    fs::create_dir(root.join("sub"))?;
+    for (name, size, days_ago) in [("b", 30, 1), ("a", 20, 3), ("c", 10, 2), ("sub/z", 5, 0)] {
+        touch(&root.join(name), size, days_ago)?;
+    }
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_keys() -> TestResult {
+    let dir = sort_tree()?;
+    let sorted = |args: &[&str], expected: &str| -> TestResult {
+        Command::cargo_bin(PRG)?
+            .current_dir(dir.path())
+            .args(args)
+            .args(["-type", "f"])
+            .assert()
+            .success()
+            .stdout(expected.to_string());
+        Ok(())
+    };
+    for jobs in [&[][..], &["-j", "3"]] {
+        let args = |args: &[&'static str]| [jobs, args].concat();
+        sorted(&args(&["--sort=name", "."]), "./a\n./b\n./c\n./sub/z\n")?;
+        sorted(&args(&["--sort=path", "."]), "./a\n./b\n./c\n./sub/z\n")?;
+        sorted(&args(&["--sort=size", "."]), "./c\n./a\n./b\n./sub/z\n")?;
+        sorted(&args(&["--sort=mtime", "."]), "./a\n./c\n./b\n./sub/z\n")?;
+        sorted(
+            &args(&["--sort=mtime", "--reverse", "."]),
+            "./sub/z\n./b\n./c\n./a\n",
+        )?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_path_orders_roots() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--sort=path", "--reverse", "tests/inputs/d", "tests/inputs/f", "-name", "*.txt"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/f/f.txt\ntests/inputs/d/d.txt\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_reverse_without_sort() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--reverse", "tests/inputs"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--sort[=<KEY>]"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn overlapping_roots() -> TestResult {
+    let dir = sort_tree()?;
+    // what is below another path, or the same path again, is walked once
+    for roots in [&["sub", "."][..], &[".", "sub"], &[".", "./", "sub/z"]] {
+        Command::cargo_bin(PRG)?
+            .current_dir(dir.path())
+            .args(roots)
+            .args(["--sort=name", "-type", "f"])
+            .assert()
+            .success()
+            .stdout("./a\n./b\n./c\n./sub/z\n");
+    }
+    // unless the other one does not go that deep
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args([".", "sub", "--max-depth", "1", "--sort=name", "-type", "f"])
+        .assert()
+        .success()
+        .stdout("./a\n./b\n./c\nsub/z\n");
+    // or leaves it out
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args([".", "sub", "--no-hidden", "--sort=name", "-name", "z"])
+        .assert()
+        .success()
+        .stdout("./sub/z\n");
+    // or prunes it, so both are walked with -prune and -depth
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args([".", "sub", "--sort=name", "-path", "./sub", "-prune", "-o", "-type", "f"])
+        .args(["-print"])
+        .assert()
+        .success()
+        .stdout("./a\n./b\n./c\nsub/z\n");
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args([".", "sub", "--sort=name", "-depth", "-type", "f"])
+        .assert()
+        .success()
+        .stdout("./a\n./b\n./c\n./sub/z\nsub/z\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn tree() -> TestResult {
+    let expected = "\
+tests/inputs
+├── a
+│   └── b
+│       ├── b.csv
+│       └── c
+│           └── c.mp3
+└── d
+    ├── b.csv
+    └── e
+        └── e.mp3
+";
+    for jobs in ["1", "3"] {
+        Command::cargo_bin(PRG)?
+            .args(["-j", jobs, "--tree", "--sort=name", "tests/inputs"])
+            .args(["-name", "*.csv", "-path", "*/?/*", "-o", "-name", "*.mp3"])
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    // -print0 and the like are left alone
+    Command::cargo_bin(PRG)?
+        .args(["--tree", "tests/inputs/f", "-print0"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/f\0tests/inputs/f/f.txt\0");
+    Ok(())
+}
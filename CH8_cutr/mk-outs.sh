@@ -5,6 +5,14 @@ set -u
 CSV="tests/inputs/movies1.csv"
 TSV="tests/inputs/movies1.tsv"
 BOOKS="tests/inputs/books.tsv"
+MIXED="tests/inputs/mixed.tsv"
+QUOTED="tests/inputs/quoted.csv"
+CONTACTS="tests/inputs/contacts.csv"
+BOX="tests/inputs/box.txt"
+COLONS="tests/inputs/colons.txt"
+DF="tests/inputs/df.txt"
+LATIN1="tests/inputs/latin1.txt"
+RECORDS="tests/inputs/records.z"
 OUT_DIR="tests/expected"
 
 [[ ! -d "$OUT_DIR" ]] && mkdir -p "$OUT_DIR"
@@ -25,4 +33,107 @@ for POS in 1 2 8 1-2 2-3 1-8; do
     cut -c $POS $CSV > "$OUT_DIR/$(basename $CSV).c${POS}.out"
 done
 
-echo -e "AA\nÉÉ\nSS\nJJ" > "$OUT_DIR/books.c1,1.out"
+# GNU cut counts bytes for -c, so these are written out
+echo -e "A\nÉ\nS\nJ" > "$OUT_DIR/books.c1,1.out"
+echo -e "AA\nÉÉ\nSS\nJJ" > "$OUT_DIR/books.c1,1.keep-order.out"
+
+for FLD in 2- -2 3,1 3,1-2 2-2; do
+    cut -f $FLD      $TSV > "$OUT_DIR/$(basename $TSV).f${FLD}.out"
+    cut -f $FLD -d , $CSV > "$OUT_DIR/$(basename $CSV).f${FLD}.dcomma.out"
+done
+
+for POS in -3 5- 3,1 2-2; do
+    cut -b $POS $TSV > "$OUT_DIR/$(basename $TSV).b${POS}.out"
+done
+
+# only ASCII is picked, where bytes are characters
+for POS in -3 3,1; do
+    cut -c $POS $TSV > "$OUT_DIR/$(basename $TSV).c${POS}.out"
+done
+
+for FLD in 2 1,3; do
+    cut -f $FLD --complement      $TSV > "$OUT_DIR/$(basename $TSV).f${FLD}.complement.out"
+    cut -f $FLD --complement -d , $CSV > "$OUT_DIR/$(basename $CSV).f${FLD}.dcomma.complement.out"
+done
+cut -b 2-3 --complement $TSV > "$OUT_DIR/$(basename $TSV).b2-3.complement.out"
+cut -c 1-2 --complement $TSV > "$OUT_DIR/$(basename $TSV).c1-2.complement.out"
+
+cut -f 1,3 --output-delimiter " | " $TSV > "$OUT_DIR/$(basename $TSV).f1,3.odelim.out"
+cut -f 3,1 -d , --output-delimiter $'\t' $CSV > "$OUT_DIR/$(basename $CSV).f3,1.dcomma.odelim.out"
+cut -c 1,3-4,6 --output-delimiter : $TSV > "$OUT_DIR/$(basename $TSV).c1,3-4,6.odelim.out"
+cut -b 1-2,3,9- --output-delimiter : $TSV > "$OUT_DIR/$(basename $TSV).b1-2,3,9-.odelim.out"
+
+cut -f 2    $MIXED > "$OUT_DIR/$(basename $MIXED).f2.out"
+cut -f 2 -s $MIXED > "$OUT_DIR/$(basename $MIXED).f2.s.out"
+
+# the default raw mode splits like cut; --mode csv has no GNU counterpart
+for FLD in 2 2- 1,3; do
+    cut -f $FLD -d , $QUOTED > "$OUT_DIR/$(basename $QUOTED).f${FLD}.dcomma.out"
+done
+
+cat > "$OUT_DIR/$(basename $QUOTED).f2.dcomma.csv.out" << 'EOF'
+quote
+"Hello, world"
+plain
+"She said ""hi"""
+EOF
+
+cat > "$OUT_DIR/$(basename $QUOTED).f2-.dcomma.csv.out" << 'EOF'
+quote,year
+"Hello, world",1843
+plain,1900
+"She said ""hi""",2000
+EOF
+
+cat > "$OUT_DIR/$(basename $QUOTED).f1,3.dcomma.csv.out" << 'EOF'
+name,year
+Ada,1843
+"Smith, J",1900
+Bob,2000
+EOF
+
+cat > "$OUT_DIR/$(basename $QUOTED).f1,3.dcomma.csv.odelim.out" << 'EOF'
+name;year
+Ada;1843
+Smith, J;1900
+Bob;2000
+EOF
+
+# --header names, with the positions they stand for
+cut -d , -f 2,3 $CONTACTS > "$OUT_DIR/$(basename $CONTACTS).header.name,email.out"
+cut -d , -f 1-3 $CONTACTS > "$OUT_DIR/$(basename $CONTACTS).header.id-email.out"
+cut -d , -f 3-  $CONTACTS > "$OUT_DIR/$(basename $CONTACTS).header.email-.out"
+cut -d , -f 5,6 $CONTACTS > "$OUT_DIR/$(basename $CONTACTS).header.regex.out"
+cut -d , -f 1,5,6 --complement $CONTACTS > "$OUT_DIR/$(basename $CONTACTS).header.id.regex.complement.out"
+(cut -d , -f 2 $CONTACTS; cut -d , -f 1 $QUOTED) > "$OUT_DIR/header.name.out"
+
+# GNU cut takes only single-byte delimiters, so turn them into tabs first
+sed 's/│/\t/g' $BOX | cut -f 2 > "$OUT_DIR/$(basename $BOX).f2.dbox.out"
+sed 's/│/\t/g' $BOX | cut -f 1,3 --output-delimiter " | " > "$OUT_DIR/$(basename $BOX).f3,1.dbox.odelim.out"
+sed 's/::/\t/g' $COLONS | cut -f 2- --output-delimiter :: > "$OUT_DIR/$(basename $COLONS).f2-.dcolons.out"
+
+# --delimiter-regex ' +' is a single space after squeezing
+tr -s ' ' < $DF | cut -d ' ' -f 1,5 > "$OUT_DIR/$(basename $DF).f1,5.dregex.out"
+tr -s ' ' < $DF | cut -d ' ' -f 6- --output-delimiter , > "$OUT_DIR/$(basename $DF).f6-.dregex.odelim.out"
+
+# -b works on bytes, whatever the encoding
+printf 'caf\xe9\tau lait\nna\xefve\tart\n' > $LATIN1
+for POS in 4 1-4; do
+    cut -b $POS $LATIN1 > "$OUT_DIR/$(basename $LATIN1).b${POS}.out"
+done
+cut -f 1 $LATIN1 > "$OUT_DIR/$(basename $LATIN1).f1.out"
+# -c turns bytes that are not UTF-8 into U+FFFD where GNU cut keeps them, so
+# this is written out
+printf 'caf\xef\xbf\xbd\nna\xef\xbf\xbdv\n' > "$OUT_DIR/$(basename $LATIN1).c1-4.out"
+
+# GNU cut ignores -n, so these are written out
+echo -e "A\n\nS\nJ" > "$OUT_DIR/books.b1.n.out"
+echo -e "Au:t\nÉ:m\nSa:m\nJu:l" > "$OUT_DIR/books.b1-2,3.n.odelim.out"
+
+# NUL-terminated records, one with a newline in it
+printf 'a\tb\nc\0d\te\0f\0' > $RECORDS
+cut -z -f 2    $RECORDS > "$OUT_DIR/$(basename $RECORDS).f2.out"
+cut -z -f 2 -s $RECORDS > "$OUT_DIR/$(basename $RECORDS).f2.s.out"
+cut -z -b 1-3  $RECORDS > "$OUT_DIR/$(basename $RECORDS).b1-3.out"
+cut -z -c 2    $RECORDS > "$OUT_DIR/$(basename $RECORDS).c2.out"
+printf '"b\nc"\0e\0f\0' > "$OUT_DIR/$(basename $RECORDS).f2.csv.out"
@@ -1,16 +1,28 @@
-use clap::Parser;
-use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use clap::{Parser, ValueEnum};
+use csv::{ReaderBuilder, Terminator};
+use regex::{bytes, Regex};
 use std::{
     error::Error,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, Write},
     ops::Range,
+    str,
 };
 
 type MyResult<T> = Result<T, Box<dyn Error + Send + Sync + 'static>>;
 
 type PositionList = Vec<Range<usize>>;
 
+type FieldList = Vec<Field>;
+
+/// An item of the --fields list: positions, or a column name or name range
+/// that is looked up in the header of each file
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Pos(Range<usize>),
+    Name(String),
+}
+
 #[derive(Debug, Parser)]
 #[command(
     about = "Rust cut",
@@ -23,55 +35,136 @@ pub struct Config {
     #[arg(value_name = "FILES", default_value = "-")]
     files: Vec<String>,
 
-    /// Field delimiter
-    #[arg(short, long, value_name = "DELIMITER", default_value = "\t")]
-    delimiter: char,
+    /// Field delimiter, any string
+    #[arg(short, long, value_name = "DELIMITER", default_value = "\t", value_parser = parse_delimiter)]
+    delimiter: String,
+
+    /// Split fields where REGEX matches, such as \s+ for aligned columns
+    #[arg(long, value_name = "REGEX", conflicts_with = "delimiter")]
+    delimiter_regex: Option<bytes::Regex>,
+
+    /// Selected fields, by position or, with --header, by name
+    #[arg(short, long, value_name = "FILEDS", conflicts_with_all = ["bytes", "chars"], value_parser = parse_fields, allow_hyphen_values = true)]
+    fields: Option<FieldList>,
 
-    /// Selected fields
-    #[arg(short, long, value_name = "FILEDS", conflicts_with_all = ["bytes", "chars"], value_parser = parse_pos)]
-    fields: Option<PositionList>,
+    /// Also select the fields whose name matches REGEX
+    #[arg(long, value_name = "REGEX", conflicts_with_all = ["bytes", "chars"])]
+    fields_regex: Option<Regex>,
+
+    /// Take the first line of each file as the field names
+    #[arg(long)]
+    header: bool,
 
     /// Selected bytes
-    #[arg(short, long, value_name = "BYTES", conflicts_with = "chars", value_parser = parse_pos)]
+    #[arg(short, long, value_name = "BYTES", conflicts_with = "chars", value_parser = parse_pos, allow_hyphen_values = true)]
     bytes: Option<PositionList>,
 
     /// Selected characters
-    #[arg(short, long, value_name = "CHARS", value_parser = parse_pos)]
+    #[arg(short, long, value_name = "CHARS", value_parser = parse_pos, allow_hyphen_values = true)]
     chars: Option<PositionList>,
+
+    /// Output positions in the order given, repeats included
+    #[arg(long)]
+    keep_order: bool,
+
+    /// Select what is not in the list instead
+    #[arg(long)]
+    complement: bool,
+
+    /// Do not print lines without the delimiter
+    #[arg(short = 's', long)]
+    only_delimited: bool,
+
+    /// Split fields on every delimiter like cut, or read them as CSV, where
+    /// quotes can hold the delimiter and output fields are quoted as needed
+    #[arg(value_enum, long, value_name = "MODE", default_value_t = Mode::Raw)]
+    mode: Mode,
+
+    /// Put STRING between fields, by default the delimiter or a space with
+    /// --delimiter-regex, and between byte or character ranges, by default
+    /// nothing
+    #[arg(long, value_name = "STRING")]
+    output_delimiter: Option<String>,
+
+    /// With --bytes, do not split multibyte characters
+    #[arg(short = 'n')]
+    no_split: bool,
+
+    /// End records with NUL instead of newline, on input and output
+    #[arg(short, long)]
+    zero_terminated: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+enum Mode {
+    Raw,
+    Csv,
 }
 
 pub fn get_args() -> MyResult<Config> {
-    let conf = Config::parse();
-    if conf.fields.is_none() && conf.bytes.is_none() && conf.chars.is_none() {
+    let mut conf = Config::parse();
+    let by_field = conf.fields.is_some() || conf.fields_regex.is_some();
+    if !by_field && conf.bytes.is_none() && conf.chars.is_none() {
         return Err(From::from("Must have --fields, --bytes, or --chars"));
     }
+    if conf.only_delimited && !by_field {
+        return Err(From::from(
+            "Suppressing non-delimited lines makes sense only with --fields",
+        ));
+    }
+    if conf.header && !by_field {
+        return Err(From::from("--header makes sense only with --fields"));
+    }
+    if conf.fields_regex.is_some() && !conf.header {
+        return Err(From::from("--fields-regex needs --header"));
+    }
+    if conf.mode == Mode::Csv {
+        if conf.delimiter_regex.is_some() {
+            return Err(From::from(
+                "--delimiter-regex cannot be used with --mode csv",
+            ));
+        }
+        if conf.delimiter.len() != 1 {
+            return Err(From::from(format!(
+                "--mode csv needs a single-byte delimiter, not \"{}\"",
+                conf.delimiter
+            )));
+        }
+    }
+    if !conf.header {
+        if let Some(Field::Name(name)) = conf
+            .fields
+            .iter()
+            .flatten()
+            .find(|field| matches!(field, Field::Name(_)))
+        {
+            return Err(From::from(format!("illegal list value: \"{name}\"")));
+        }
+    }
+    for positions in [&mut conf.bytes, &mut conf.chars].into_iter().flatten() {
+        *positions = arrange_pos(positions, conf.keep_order, conf.complement);
+    }
     Ok(conf)
 }
 
 pub fn run(conf: Config) -> MyResult<()> {
-    for filename in conf.files {
-        match open(&filename) {
+    let field_delimiter = match (&conf.output_delimiter, &conf.delimiter_regex) {
+        (Some(delimiter), _) => delimiter.clone(),
+        (None, Some(_)) => " ".to_string(),
+        (None, None) => conf.delimiter.clone(),
+    };
+    let range_delimiter = conf.output_delimiter.as_deref().unwrap_or("");
+    let mut out = io::stdout().lock();
+    for filename in &conf.files {
+        match open(filename) {
             Err(e) => eprintln!("{}: {}", filename, e),
             Ok(file) => {
-                if let Some(p) = &conf.chars {
-                    for line in file.lines() {
-                        println!("{}", extract_chars(&line?, p));
-                    }
-                } else if let Some(p) = &conf.bytes {
-                    for line in file.lines() {
-                        println!("{}", extract_bytes(&line?, p));
-                    }
-                } else if let Some(p) = &conf.fields {
-                    let mut reader = ReaderBuilder::new()
-                        .delimiter(conf.delimiter as u8)
-                        .has_headers(false)
-                        .from_reader(file);
-                    let mut wtr = WriterBuilder::new()
-                        .delimiter(conf.delimiter as u8)
-                        .from_writer(io::stdout());
-                    for record in reader.records() {
-                        let record = record?;
-                        wtr.write_record(extract_fields(&record, p))?;
+                if conf.bytes.is_some() || conf.chars.is_some() {
+                    cut_ranges(file, &conf, range_delimiter, &mut out)?;
+                } else {
+                    match conf.mode {
+                        Mode::Raw => cut_raw(file, &conf, &field_delimiter, &mut out)?,
+                        Mode::Csv => cut_csv(file, &conf, &field_delimiter, &mut out)?,
                     }
                 }
             }
@@ -80,6 +173,196 @@ pub fn run(conf: Config) -> MyResult<()> {
     Ok(())
 }
 
+/// The byte that ends each record.
+fn terminator(conf: &Config) -> u8 {
+    if conf.zero_terminated {
+        b'\0'
+    } else {
+        b'\n'
+    }
+}
+
+/// Cuts --bytes from each record as it is, or --chars from it as UTF-8,
+/// with any bytes that are not UTF-8 turned into U+FFFD.
+fn cut_ranges(
+    mut file: Box<dyn BufRead>,
+    conf: &Config,
+    output_delimiter: &str,
+    out: &mut impl Write,
+) -> MyResult<()> {
+    let terminator = terminator(conf);
+    let mut buf = vec![];
+    while file.read_until(terminator, &mut buf)? > 0 {
+        let line = buf.strip_suffix(&[terminator]).unwrap_or(&buf);
+        if let Some(p) = &conf.bytes {
+            let bytes = extract_bytes(line, p, output_delimiter.as_bytes(), conf.no_split);
+            out.write_all(&bytes)?;
+        } else if let Some(p) = &conf.chars {
+            let chars = extract_chars(&String::from_utf8_lossy(line), p, output_delimiter);
+            out.write_all(chars.as_bytes())?;
+        }
+        out.write_all(&[terminator])?;
+        buf.clear();
+    }
+    Ok(())
+}
+
+/// Splits each line on every delimiter or match of --delimiter-regex, as
+/// bytes, and writes the fields out as they are. Like GNU cut, a line without
+/// the delimiter is printed whole unless -s.
+fn cut_raw(
+    mut file: Box<dyn BufRead>,
+    conf: &Config,
+    output_delimiter: &str,
+    out: &mut impl Write,
+) -> MyResult<()> {
+    let terminator = terminator(conf);
+    let mut field_pos = None;
+    let mut buf = vec![];
+    while file.read_until(terminator, &mut buf)? > 0 {
+        let line = buf.strip_suffix(&[terminator]).unwrap_or(&buf);
+        let fields: Vec<&[u8]> = match &conf.delimiter_regex {
+            Some(regex) => regex.split(line).collect(),
+            None => split_bytes(line, conf.delimiter.as_bytes()),
+        };
+        let field_pos = match &mut field_pos {
+            Some(field_pos) => field_pos,
+            None => {
+                let names: Vec<_> = fields.iter().map(|f| String::from_utf8_lossy(f)).collect();
+                field_pos.insert(select_fields(conf, &names)?)
+            }
+        };
+        if fields.len() > 1 {
+            out.write_all(&extract_fields(&fields, field_pos).join(output_delimiter.as_bytes()))?;
+            out.write_all(&[terminator])?;
+        } else if !conf.only_delimited {
+            out.write_all(line)?;
+            out.write_all(&[terminator])?;
+        }
+        buf.clear();
+    }
+    Ok(())
+}
+
+/// Reads records with the csv crate, so quoted fields can hold the delimiter
+/// and line breaks, and writes them quoted where needed. Blank lines are
+/// skipped.
+fn cut_csv(
+    file: Box<dyn BufRead>,
+    conf: &Config,
+    output_delimiter: &str,
+    out: &mut impl Write,
+) -> MyResult<()> {
+    let terminator = terminator(conf);
+    let mut reader = ReaderBuilder::new()
+        .delimiter(conf.delimiter.as_bytes()[0])
+        .terminator(if conf.zero_terminated {
+            Terminator::Any(terminator)
+        } else {
+            Terminator::CRLF
+        })
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(file);
+    let mut field_pos = None;
+    for record in reader.records() {
+        let record = record?;
+        let fields: Vec<&str> = record.iter().collect();
+        let field_pos = match &mut field_pos {
+            Some(field_pos) => field_pos,
+            None => field_pos.insert(select_fields(conf, &fields)?),
+        };
+        if fields.len() > 1 {
+            let fields = extract_fields(&fields, field_pos);
+            write_fields(out, &fields, output_delimiter, terminator)?;
+        } else if !conf.only_delimited {
+            write_fields(out, &fields, output_delimiter, terminator)?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_delimiter(delimiter: &str) -> MyResult<String> {
+    if delimiter.is_empty() {
+        return Err(From::from("the delimiter must not be empty"));
+    }
+    Ok(delimiter.to_string())
+}
+
+/// Positions are parsed as for --bytes; an item with a letter in it is kept
+/// as a name for --header.
+fn parse_fields(list: &str) -> MyResult<FieldList> {
+    let mut result = vec![];
+    for item in list.split(',') {
+        match parse_pos(item) {
+            Ok(positions) => result.extend(positions.into_iter().map(Field::Pos)),
+            Err(_) if item.contains(char::is_alphabetic) => {
+                result.push(Field::Name(item.to_string()))
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(result)
+}
+
+/// Resolves --fields and --fields-regex against the column names, which are
+/// only looked at with --header, and puts the positions in output order.
+fn select_fields<T: AsRef<str>>(conf: &Config, names: &[T]) -> MyResult<PositionList> {
+    let names: Vec<&str> = if conf.header {
+        names.iter().map(AsRef::as_ref).collect()
+    } else {
+        vec![]
+    };
+    let mut positions: PositionList = vec![];
+    for field in conf.fields.iter().flatten() {
+        match field {
+            Field::Pos(range) => positions.push(range.clone()),
+            Field::Name(name) => positions.push(name_pos(name, &names)?),
+        }
+    }
+    if let Some(regex) = &conf.fields_regex {
+        positions.extend(
+            (0..names.len())
+                .filter(|&i| regex.is_match(names[i]))
+                .map(|i| i..i + 1),
+        );
+    }
+    Ok(arrange_pos(&positions, conf.keep_order, conf.complement))
+}
+
+/// The column called `name`, or the columns from one name to another, where
+/// either end may be a number or left out as in "-N" and "N-".
+fn name_pos(name: &str, names: &[&str]) -> MyResult<Range<usize>> {
+    let column = |name: &str| match names.iter().position(|n| *n == name) {
+        Some(i) => Some(i),
+        None => name.parse::<usize>().ok().filter(|&n| n > 0).map(|n| n - 1),
+    };
+    if let Some(i) = column(name) {
+        return Ok(i..i + 1);
+    }
+    // names can hold a hyphen themselves, so try every split
+    let range = name.match_indices('-').find_map(|(i, _)| {
+        let (first, last) = (&name[..i], &name[i + 1..]);
+        let start = if first.is_empty() { 0 } else { column(first)? };
+        let end = if last.is_empty() {
+            usize::MAX
+        } else {
+            column(last)? + 1
+        };
+        Some((first, last, start..end))
+    });
+    match range {
+        Some((first, last, range)) if range.end <= range.start => Err(From::from(format!(
+            "First column in range ({first}) must come before second column ({last})"
+        ))),
+        Some((_, _, range)) => Ok(range),
+        None => Err(From::from(format!(
+            "unknown column \"{name}\", available columns: {}",
+            names.join(", ")
+        ))),
+    }
+}
+
 pub fn parse_pos(range: &str) -> MyResult<PositionList> {
     let mut result: Vec<Range<usize>> = vec![];
     let ranges: Vec<&str> = range.split(',').collect();
@@ -91,6 +374,21 @@ pub fn parse_pos(range: &str) -> MyResult<PositionList> {
         let mut start: usize;
         let end: usize;
         match temp.len() {
+            // "N-" up to the end of the line and "-N" from its start
+            2 if temp[0].is_empty() != temp[1].is_empty() => {
+                let bound = if temp[0].is_empty() { temp[1] } else { temp[0] };
+                let n = match bound.parse::<usize>() {
+                    Ok(n) if n > 0 => n,
+                    _ => {
+                        return Err(From::from(format!("illegal list value: \"{}\"", range)));
+                    }
+                };
+                (start, end) = if temp[0].is_empty() {
+                    (0, n)
+                } else {
+                    (n - 1, usize::MAX)
+                };
+            }
             1 => {
                 end = match temp[0].parse::<usize>() {
                     Ok(n) if n > 0 => n,
@@ -113,7 +411,7 @@ pub fn parse_pos(range: &str) -> MyResult<PositionList> {
                         return Err(From::from(format!("illegal list value: \"{}\"", temp[1])));
                     }
                 };
-                if end <= start {
+                if end < start {
                     return Err(From::from(format!(
                         "First number in range ({start}) must be lower than second number ({end})"
                     )));
@@ -130,6 +428,51 @@ pub fn parse_pos(range: &str) -> MyResult<PositionList> {
     Ok(result)
 }
 
+/// Merges the positions unless --keep-order, and turns them around for
+/// --complement.
+fn arrange_pos(positions: &[Range<usize>], keep_order: bool, complement: bool) -> PositionList {
+    let mut result = positions.to_vec();
+    if !keep_order || complement {
+        result = merge_pos(&result);
+    }
+    if complement {
+        result = complement_pos(&result);
+    }
+    result
+}
+
+/// Sorts the positions and merges those that overlap, so that each is output
+/// once and in input order, like GNU cut. Ranges that only touch stay apart
+/// for --output-delimiter.
+fn merge_pos(positions: &[Range<usize>]) -> PositionList {
+    let mut sorted = positions.to_vec();
+    sorted.sort_by_key(|r| r.start);
+    let mut result: PositionList = vec![];
+    for range in sorted {
+        match result.last_mut() {
+            Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+            _ => result.push(range),
+        }
+    }
+    result
+}
+
+/// The gaps between the merged positions, up to the end of the line.
+fn complement_pos(positions: &[Range<usize>]) -> PositionList {
+    let mut result: PositionList = vec![];
+    let mut start = 0;
+    for range in positions {
+        if range.start > start {
+            result.push(start..range.start);
+        }
+        start = range.end;
+    }
+    if start < usize::MAX {
+        result.push(start..usize::MAX);
+    }
+    result
+}
+
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     match filename {
         "-" => Ok(Box::new(BufReader::new(io::stdin()))),
@@ -137,7 +480,7 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
-fn extract_chars(line: &str, char_pos: &[Range<usize>]) -> String {
+fn extract_chars(line: &str, char_pos: &[Range<usize>], delimiter: &str) -> String {
     char_pos
         .iter()
         .map(|r| {
@@ -146,36 +489,96 @@ fn extract_chars(line: &str, char_pos: &[Range<usize>]) -> String {
                 .take(r.end - r.start)
                 .collect::<String>()
         })
-        .collect()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(delimiter)
 }
 
-fn extract_bytes(line: &str, byte_pos: &[Range<usize>]) -> String {
+/// With `whole_chars`, as for -n, a range takes in the character its start
+/// falls in and leaves out the one its end falls in, so that a character is
+/// output when its last byte is selected. Bytes that are not UTF-8 count as
+/// characters of their own.
+fn extract_bytes(
+    line: &[u8],
+    byte_pos: &[Range<usize>],
+    delimiter: &[u8],
+    whole_chars: bool,
+) -> Vec<u8> {
+    let char_start = |mut i: usize| {
+        while whole_chars && i > 0 && i < line.len() && line[i] & 0xc0 == 0x80 {
+            i -= 1;
+        }
+        i
+    };
     byte_pos
         .iter()
         .map(|r| {
-            String::from_utf8_lossy(
-                line.bytes()
-                    .skip(r.start)
-                    .take(r.end - r.start)
-                    .collect::<Vec<u8>>()
-                    .as_slice(),
-            )
-            .into_owned()
+            let end = char_start(r.end.min(line.len()));
+            let start = char_start(r.start.min(end));
+            &line[start..end]
         })
-        .collect()
+        .filter(|bytes| !bytes.is_empty())
+        .collect::<Vec<_>>()
+        .join(delimiter)
 }
 
-fn extract_fields<'a>(record: &'a StringRecord, filed_pos: &[Range<usize>]) -> Vec<&'a str> {
+fn extract_fields<'a, T: ?Sized>(fields: &[&'a T], filed_pos: &[Range<usize>]) -> Vec<&'a T> {
     filed_pos
         .iter()
-        .cloned()
-        .flat_map(|range| range.filter_map(|i| record.get(i)))
+        .flat_map(|r| fields.iter().skip(r.start).take(r.end - r.start).copied())
         .collect()
 }
 
+/// Every piece of `line` between delimiters, as cut sees them.
+fn split_bytes<'a>(line: &'a [u8], delimiter: &[u8]) -> Vec<&'a [u8]> {
+    let mut fields = vec![];
+    let mut start = 0;
+    let mut i = 0;
+    while i + delimiter.len() <= line.len() {
+        if line[i..].starts_with(delimiter) {
+            fields.push(&line[start..i]);
+            i += delimiter.len();
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+    fields.push(&line[start..]);
+    fields
+}
+
+/// Writes the fields as a record, quoting those with the delimiter, a quote
+/// or a line break in them the way the csv crate does.
+fn write_fields(
+    out: &mut impl Write,
+    fields: &[&str],
+    delimiter: &str,
+    terminator: u8,
+) -> io::Result<()> {
+    let line = fields
+        .iter()
+        .map(|field| {
+            if (!delimiter.is_empty() && field.contains(delimiter))
+                || field.contains(['"', '\n', '\r'])
+            {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(delimiter);
+    out.write_all(line.as_bytes())?;
+    out.write_all(&[terminator])
+}
+
 #[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
 mod unit_tests {
-    use super::{extract_bytes, extract_chars, parse_pos};
+    use super::{
+        complement_pos, extract_bytes, extract_chars, extract_fields, merge_pos, name_pos,
+        parse_fields, parse_pos, split_bytes, write_fields, Field, PositionList,
+    };
 
     #[test]
     fn test_parse_pos() {
@@ -226,8 +629,9 @@ mod unit_tests {
         let res = parse_pos("1,");
         assert!(res.is_err());
 
-        let res = parse_pos("1-");
+        let res = parse_pos("-0");
         assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"-0\"");
 
         let res = parse_pos("1-1-1");
         assert!(res.is_err());
@@ -235,13 +639,6 @@ mod unit_tests {
         let res = parse_pos("1-1-a");
         assert!(res.is_err());
 
-        let res = parse_pos("1-1");
-        assert!(res.is_err());
-        assert_eq!(
-            res.unwrap_err().to_string(),
-            "First number in range (1) must be lower than second number (1)"
-        );
-
         let res = parse_pos("2-1");
         assert!(res.is_err());
         assert_eq!(
@@ -280,25 +677,172 @@ mod unit_tests {
         let res = parse_pos("15,19-20");
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), vec![14..15, 18..20]);
+
+        let res = parse_pos("1-1");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..1]);
+
+        let res = parse_pos("3-");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![2..usize::MAX]);
+
+        let res = parse_pos("-5");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..5]);
+
+        let res = parse_pos("-2,4-,3");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..2, 3..usize::MAX, 2..3]);
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let res = parse_fields("1,3-");
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            vec![Field::Pos(0..1), Field::Pos(2..usize::MAX)]
+        );
+
+        let res = parse_fields("id-email,2");
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            vec![Field::Name("id-email".to_string()), Field::Pos(1..2)]
+        );
+
+        let res = parse_fields("name,0");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"0\"");
+
+        let res = parse_fields("2-1,name");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_name_pos() {
+        let names = ["id", "e-mail", "name"];
+        assert_eq!(name_pos("id", &names).unwrap(), 0..1);
+        assert_eq!(name_pos("e-mail", &names).unwrap(), 1..2);
+        assert_eq!(name_pos("id-name", &names).unwrap(), 0..3);
+        assert_eq!(name_pos("-e-mail", &names).unwrap(), 0..2);
+        assert_eq!(name_pos("e-mail-", &names).unwrap(), 1..usize::MAX);
+        assert_eq!(name_pos("2-name", &names).unwrap(), 1..3);
+        assert_eq!(
+            name_pos("name-id", &names).unwrap_err().to_string(),
+            "First column in range (name) must come before second column (id)"
+        );
+        assert_eq!(
+            name_pos("mail", &names).unwrap_err().to_string(),
+            "unknown column \"mail\", available columns: id, e-mail, name"
+        );
+    }
+
+    #[test]
+    fn test_merge_pos() {
+        assert_eq!(merge_pos(&[]), PositionList::new());
+        assert_eq!(merge_pos(&[2..3, 0..1]), vec![0..1, 2..3]);
+        assert_eq!(merge_pos(&[0..1, 0..1]), vec![0..1]);
+        assert_eq!(merge_pos(&[0..1, 1..2]), vec![0..1, 1..2]);
+        assert_eq!(merge_pos(&[0..2, 1..2]), vec![0..2]);
+        assert_eq!(merge_pos(&[4..usize::MAX, 0..3, 2..6]), vec![0..usize::MAX]);
+        assert_eq!(merge_pos(&[6..7, 0..2, 1..3]), vec![0..3, 6..7]);
+    }
+
+    #[test]
+    fn test_complement_pos() {
+        assert_eq!(complement_pos(&[]), vec![0..usize::MAX]);
+        assert_eq!(complement_pos(&[0..1]), vec![1..usize::MAX]);
+        assert_eq!(complement_pos(&[1..3]), vec![0..1, 3..usize::MAX]);
+        assert_eq!(
+            complement_pos(&[0..1, 2..3, 5..usize::MAX]),
+            vec![1..2, 3..5]
+        );
+        assert_eq!(complement_pos(&[0..usize::MAX]), PositionList::new());
+    }
+
+    #[test]
+    fn test_split_bytes() {
+        let split = |line: &str, delimiter: &str| -> Vec<String> {
+            split_bytes(line.as_bytes(), delimiter.as_bytes())
+                .iter()
+                .map(|field| String::from_utf8_lossy(field).into_owned())
+                .collect()
+        };
+        assert_eq!(split("", ","), [""]);
+        assert_eq!(split("a", ","), ["a"]);
+        assert_eq!(split("a,\"b,c\"", ","), ["a", "\"b", "c\""]);
+        assert_eq!(split(",a,,", ","), ["", "a", "", ""]);
+        assert_eq!(split("a::b:c", "::"), ["a", "b:c"]);
+        assert_eq!(split("éaéb", "é"), ["", "a", "b"]);
+    }
+
+    #[test]
+    fn test_extract_fields() {
+        let fields = ["a", "b", "c"];
+        assert_eq!(extract_fields(&fields, &[0..1]), ["a"]);
+        assert_eq!(extract_fields(&fields, &[2..3, 0..2]), ["c", "a", "b"]);
+        assert_eq!(extract_fields(&fields, &[1..usize::MAX]), ["b", "c"]);
+        assert_eq!(extract_fields(&fields, &[3..4]), [""; 0]);
+    }
+
+    #[test]
+    fn test_write_fields() {
+        let write = |fields: &[&str], delimiter| {
+            let mut out = vec![];
+            write_fields(&mut out, fields, delimiter, b'\n').unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(write(&[], "\t"), "\n");
+        assert_eq!(write(&["a", "b"], "\t"), "a\tb\n");
+        assert_eq!(write(&["a", "b"], " | "), "a | b\n");
+        assert_eq!(write(&["a,b", "c"], ","), "\"a,b\",c\n");
+        assert_eq!(write(&["a\"b", "c"], ""), "\"a\"\"b\"c\n");
     }
 
     #[test]
     fn test_extract_chars() {
-        assert_eq!(extract_chars("", &[0..1]), "".to_string());
-        assert_eq!(extract_chars("ábc", &[0..1]), "á".to_string());
-        assert_eq!(extract_chars("ábc", &[0..1, 2..3]), "ác".to_string());
-        assert_eq!(extract_chars("ábc", &[0..3]), "ábc".to_string());
-        assert_eq!(extract_chars("ábc", &[2..3, 1..2]), "cb".to_string());
-        assert_eq!(extract_chars("ábc", &[0..1, 1..2, 4..5]), "áb".to_string());
+        assert_eq!(extract_chars("", &[0..1], ""), "".to_string());
+        assert_eq!(extract_chars("ábc", &[0..1], ""), "á".to_string());
+        assert_eq!(extract_chars("ábc", &[0..1, 2..3], ""), "ác".to_string());
+        assert_eq!(extract_chars("ábc", &[0..3], ""), "ábc".to_string());
+        assert_eq!(extract_chars("ábc", &[2..3, 1..2], ""), "cb".to_string());
+        assert_eq!(
+            extract_chars("ábc", &[0..1, 1..2, 4..5], ""),
+            "áb".to_string()
+        );
+        assert_eq!(extract_chars("ábc", &[1..usize::MAX], ""), "bc".to_string());
+        assert_eq!(
+            extract_chars("ábc", &[0..1, 1..3], ", "),
+            "á, bc".to_string()
+        );
     }
 
     #[test]
     fn test_extract_bytes() {
-        assert_eq!(extract_bytes("ábc", &[0..1]), "�".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..2]), "á".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..3]), "áb".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..4]), "ábc".to_string());
-        assert_eq!(extract_bytes("ábc", &[3..4, 2..3]), "cb".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..2, 5..6]), "á".to_string());
+        let bytes = |line: &str, pos: &[_], delimiter: &str, whole_chars| {
+            extract_bytes(line.as_bytes(), pos, delimiter.as_bytes(), whole_chars)
+        };
+        assert_eq!(bytes("ábc", &[0..1], "", false), b"\xc3");
+        assert_eq!(bytes("ábc", &[0..2], "", false), "á".as_bytes());
+        assert_eq!(bytes("ábc", &[0..3], "", false), "áb".as_bytes());
+        assert_eq!(bytes("ábc", &[0..4], "", false), "ábc".as_bytes());
+        assert_eq!(bytes("ábc", &[3..4, 2..3], "", false), b"cb");
+        assert_eq!(bytes("ábc", &[0..2, 5..6], "", false), "á".as_bytes());
+        assert_eq!(bytes("ábc", &[0..2, 3..4], ":", false), "á:c".as_bytes());
+        assert_eq!(bytes("ábc", &[0..2, 5..6], ":", false), "á".as_bytes());
+        assert_eq!(bytes("ábc", &[1..2], "", false), b"\xa1");
+        assert_eq!(bytes("ábc", &[0..1], "", true), b"");
+        assert_eq!(bytes("ábc", &[1..2], "", true), "á".as_bytes());
+        assert_eq!(bytes("ábc", &[0..3], "", true), "áb".as_bytes());
+        assert_eq!(bytes("aéé", &[2..4], "", true), "é".as_bytes());
+        assert_eq!(bytes("aéé", &[0..1, 1..2], ":", true), b"a");
+        assert_eq!(
+            bytes("a\u{ff}", &[1..usize::MAX], "", true),
+            "\u{ff}".as_bytes()
+        );
+
+        let line = b"a\xffb";
+        assert_eq!(extract_bytes(line, &[1..2], b"", true), b"\xff");
     }
 }
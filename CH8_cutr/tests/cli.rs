@@ -1,3 +1,5 @@
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
@@ -9,6 +11,14 @@ const PRG: &str = "cutr";
 const CSV: &str = "tests/inputs/movies1.csv";
 const TSV: &str = "tests/inputs/movies1.tsv";
 const BOOKS: &str = "tests/inputs/books.tsv";
+const MIXED: &str = "tests/inputs/mixed.tsv";
+const QUOTED: &str = "tests/inputs/quoted.csv";
+const CONTACTS: &str = "tests/inputs/contacts.csv";
+const BOX: &str = "tests/inputs/box.txt";
+const COLONS: &str = "tests/inputs/colons.txt";
+const DF: &str = "tests/inputs/df.txt";
+const LATIN1: &str = "tests/inputs/latin1.txt";
+const RECORDS: &str = "tests/inputs/records.z";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -93,16 +103,38 @@ fn dies_bad_digit_chars() -> TestResult {
 fn dies_empty_delimiter() -> TestResult {
     dies(
         &[CSV, "-f", "1", "-d", ""],
-        "invalid value '' for '--delimiter <DELIMITER>': cannot parse char from empty string",
+        "invalid value '' for '--delimiter <DELIMITER>': the delimiter must not be empty",
     )
 }
 
 // --------------------------------------------------
 #[test]
-fn dies_bad_delimiter() -> TestResult {
+fn dies_csv_long_delimiter() -> TestResult {
+    dies(
+        &[CSV, "-f", "1", "-d", ",,", "--mode", "csv"],
+        "--mode csv needs a single-byte delimiter, not \",,\"",
+    )?;
     dies(
-        &[CSV, "-f", "1", "-d", ",,"],
-        "invalid value ',,' for '--delimiter <DELIMITER>': too many characters in string",
+        &[BOX, "-f", "1", "-d", "│", "--mode", "csv"],
+        "--mode csv needs a single-byte delimiter, not \"│\"",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_csv_delimiter_regex() -> TestResult {
+    dies(
+        &[CSV, "-f", "1", "--delimiter-regex", ",", "--mode", "csv"],
+        "--delimiter-regex cannot be used with --mode csv",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_delimiter_and_regex() -> TestResult {
+    dies(
+        &[CSV, "-f", "1", "-d", ",", "--delimiter-regex", ","],
+        "cannot be used with",
     )
 }
 
@@ -159,14 +191,14 @@ fn run(args: &[&str], expected_file: &str) -> TestResult {
 }
 
 // --------------------------------------------------
-fn run_lossy(args: &[&str], expected_file: &str) -> TestResult {
-    let contents = fs::read(expected_file)?;
-    let expected = String::from_utf8_lossy(&contents);
+fn run_bytes(args: &[&str], expected_file: &str) -> TestResult {
+    println!("expected {}", &expected_file);
+    let expected = fs::read(expected_file)?;
     Command::cargo_bin(PRG)?
         .args(args)
         .assert()
         .success()
-        .stdout(predicate::str::contains(expected));
+        .stdout(expected);
     Ok(())
 }
 
@@ -275,7 +307,7 @@ fn tsv_b2() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn tsv_b8() -> TestResult {
-    run_lossy(&[TSV, "-b", "8"], "tests/expected/movies1.tsv.b8.out")
+    run_bytes(&[TSV, "-b", "8"], "tests/expected/movies1.tsv.b8.out")
 }
 
 // --------------------------------------------------
@@ -293,7 +325,7 @@ fn tsv_b2_3() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn tsv_b1_8() -> TestResult {
-    run_lossy(&[TSV, "-b", "1-8"], "tests/expected/movies1.tsv.b1-8.out")
+    run_bytes(&[TSV, "-b", "1-8"], "tests/expected/movies1.tsv.b1-8.out")
 }
 
 // --------------------------------------------------
@@ -337,3 +369,663 @@ fn tsv_c1_8() -> TestResult {
 fn repeated_value() -> TestResult {
     run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1,1.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn repeated_value_keep_order() -> TestResult {
+    run(
+        &[BOOKS, "-c", "1,1", "--keep-order"],
+        "tests/expected/books.c1,1.keep-order.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_open() -> TestResult {
+    run(&[TSV, "-f", "2-"], "tests/expected/movies1.tsv.f2-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f_open_2() -> TestResult {
+    run(&[TSV, "-f", "-2"], "tests/expected/movies1.tsv.f-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f3_1() -> TestResult {
+    run(&[TSV, "-f", "3,1"], "tests/expected/movies1.tsv.f3,1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f3_1_2() -> TestResult {
+    run(
+        &[TSV, "-f", "3,1-2"],
+        "tests/expected/movies1.tsv.f3,1-2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_2() -> TestResult {
+    run(&[TSV, "-f", "2-2"], "tests/expected/movies1.tsv.f2-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f2_open() -> TestResult {
+    run(
+        &[CSV, "-f", "2-", "-d", ","],
+        "tests/expected/movies1.csv.f2-.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f3_1() -> TestResult {
+    run(
+        &[CSV, "-f", "3,1", "-d", ","],
+        "tests/expected/movies1.csv.f3,1.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f_open_2() -> TestResult {
+    run(
+        &[CSV, "-f", "-2", "-d", ","],
+        "tests/expected/movies1.csv.f-2.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f3_1_2() -> TestResult {
+    run(
+        &[CSV, "-f", "3,1-2", "-d", ","],
+        "tests/expected/movies1.csv.f3,1-2.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f2_2() -> TestResult {
+    run(
+        &[CSV, "-f", "2-2", "-d", ","],
+        "tests/expected/movies1.csv.f2-2.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b2_2() -> TestResult {
+    run(&[TSV, "-b", "2-2"], "tests/expected/movies1.tsv.b2-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b_open_3() -> TestResult {
+    run(&[TSV, "-b", "-3"], "tests/expected/movies1.tsv.b-3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b5_open() -> TestResult {
+    run(&[TSV, "-b", "5-"], "tests/expected/movies1.tsv.b5-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b3_1() -> TestResult {
+    run(&[TSV, "-b", "3,1"], "tests/expected/movies1.tsv.b3,1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c_open_3() -> TestResult {
+    run(&[TSV, "-c", "-3"], "tests/expected/movies1.tsv.c-3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c3_1() -> TestResult {
+    run(&[TSV, "-c", "3,1"], "tests/expected/movies1.tsv.c3,1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn keep_order() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([TSV, "-f", "3,1-2", "--keep-order"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("director\ttitle\tyear\n"));
+    Command::cargo_bin(PRG)?
+        .args([TSV, "-c", "3,1", "--keep-order"])
+        .assert()
+        .success()
+        .stdout("tt\neT\nsL\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_decreasing_range() -> TestResult {
+    dies(
+        &[CSV, "-f", "2-1"],
+        "First number in range (2) must be lower than second number (1)",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_complement() -> TestResult {
+    run(
+        &[TSV, "-f", "2", "--complement"],
+        "tests/expected/movies1.tsv.f2.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f1_3_complement() -> TestResult {
+    run(
+        &[TSV, "-f", "1,3", "--complement"],
+        "tests/expected/movies1.tsv.f1,3.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f2_complement() -> TestResult {
+    run(
+        &[CSV, "-f", "2", "-d", ",", "--complement"],
+        "tests/expected/movies1.csv.f2.dcomma.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f1_3_complement() -> TestResult {
+    run(
+        &[CSV, "-f", "1,3", "-d", ",", "--complement"],
+        "tests/expected/movies1.csv.f1,3.dcomma.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b2_3_complement() -> TestResult {
+    run(
+        &[TSV, "-b", "2-3", "--complement"],
+        "tests/expected/movies1.tsv.b2-3.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c1_2_complement() -> TestResult {
+    run(
+        &[TSV, "-c", "1-2", "--complement"],
+        "tests/expected/movies1.tsv.c1-2.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f1_3_output_delimiter() -> TestResult {
+    run(
+        &[TSV, "-f", "1,3", "--output-delimiter", " | "],
+        "tests/expected/movies1.tsv.f1,3.odelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f3_1_output_delimiter() -> TestResult {
+    run(
+        &[CSV, "-f", "3,1", "-d", ",", "--output-delimiter", "\t"],
+        "tests/expected/movies1.csv.f3,1.dcomma.odelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c_output_delimiter() -> TestResult {
+    run(
+        &[TSV, "-c", "1,3-4,6", "--output-delimiter", ":"],
+        "tests/expected/movies1.tsv.c1,3-4,6.odelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_b_output_delimiter() -> TestResult {
+    run_bytes(
+        &[TSV, "-b", "1-2,3,9-", "--output-delimiter", ":"],
+        "tests/expected/movies1.tsv.b1-2,3,9-.odelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn undelimited_lines() -> TestResult {
+    run(&[MIXED, "-f", "2"], "tests/expected/mixed.tsv.f2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn only_delimited() -> TestResult {
+    run(
+        &[MIXED, "-f", "2", "-s"],
+        "tests/expected/mixed.tsv.f2.s.out",
+    )?;
+    run(
+        &[MIXED, "-f", "2", "--only-delimited"],
+        "tests/expected/mixed.tsv.f2.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_only_delimited_chars() -> TestResult {
+    dies(
+        &[MIXED, "-c", "1", "-s"],
+        "Suppressing non-delimited lines makes sense only with --fields",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quoted_f2_raw() -> TestResult {
+    run(
+        &[QUOTED, "-f", "2", "-d", ","],
+        "tests/expected/quoted.csv.f2.dcomma.out",
+    )?;
+    run(
+        &[QUOTED, "-f", "2", "-d", ",", "--mode", "raw"],
+        "tests/expected/quoted.csv.f2.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quoted_f2_csv() -> TestResult {
+    run(
+        &[QUOTED, "-f", "2", "-d", ",", "--mode", "csv"],
+        "tests/expected/quoted.csv.f2.dcomma.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quoted_f2_open_raw() -> TestResult {
+    run(
+        &[QUOTED, "-f", "2-", "-d", ","],
+        "tests/expected/quoted.csv.f2-.dcomma.out",
+    )?;
+    run(
+        &[QUOTED, "-f", "2-", "-d", ",", "--mode", "raw"],
+        "tests/expected/quoted.csv.f2-.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quoted_f2_open_csv() -> TestResult {
+    run(
+        &[QUOTED, "-f", "2-", "-d", ",", "--mode", "csv"],
+        "tests/expected/quoted.csv.f2-.dcomma.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quoted_f1_3_raw() -> TestResult {
+    run(
+        &[QUOTED, "-f", "1,3", "-d", ","],
+        "tests/expected/quoted.csv.f1,3.dcomma.out",
+    )?;
+    run(
+        &[QUOTED, "-f", "1,3", "-d", ",", "--mode", "raw"],
+        "tests/expected/quoted.csv.f1,3.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quoted_f1_3_csv() -> TestResult {
+    run(
+        &[QUOTED, "-f", "1,3", "-d", ",", "--mode", "csv"],
+        "tests/expected/quoted.csv.f1,3.dcomma.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quoted_csv_output_delimiter() -> TestResult {
+    run(
+        &[
+            QUOTED,
+            "-f",
+            "1,3",
+            "-d",
+            ",",
+            "--mode",
+            "csv",
+            "--output-delimiter",
+            ";",
+        ],
+        "tests/expected/quoted.csv.f1,3.dcomma.csv.odelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_mode() -> TestResult {
+    dies(
+        &[CSV, "-f", "1", "--mode", "tsv"],
+        "invalid value 'tsv' for '--mode <MODE>'",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn header_names() -> TestResult {
+    run(
+        &[CONTACTS, "-d", ",", "--header", "-f", "name,email"],
+        "tests/expected/contacts.csv.header.name,email.out",
+    )?;
+    run(
+        &[
+            CONTACTS,
+            "-d",
+            ",",
+            "--header",
+            "--mode",
+            "csv",
+            "-f",
+            "name,email",
+        ],
+        "tests/expected/contacts.csv.header.name,email.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn header_name_range() -> TestResult {
+    run(
+        &[CONTACTS, "-d", ",", "--header", "-f", "id-email"],
+        "tests/expected/contacts.csv.header.id-email.out",
+    )?;
+    run(
+        &[
+            CONTACTS, "-d", ",", "--header", "--mode", "csv", "-f", "id-email",
+        ],
+        "tests/expected/contacts.csv.header.id-email.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn header_open_name_range() -> TestResult {
+    run(
+        &[CONTACTS, "-d", ",", "--header", "-f", "email-"],
+        "tests/expected/contacts.csv.header.email-.out",
+    )?;
+    run(
+        &[
+            CONTACTS, "-d", ",", "--header", "--mode", "csv", "-f", "email-",
+        ],
+        "tests/expected/contacts.csv.header.email-.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn header_fields_regex() -> TestResult {
+    run(
+        &[CONTACTS, "-d", ",", "--header", "--fields-regex", "^addr_"],
+        "tests/expected/contacts.csv.header.regex.out",
+    )?;
+    run(
+        &[
+            CONTACTS,
+            "-d",
+            ",",
+            "--header",
+            "--mode",
+            "csv",
+            "--fields-regex",
+            "^addr_",
+        ],
+        "tests/expected/contacts.csv.header.regex.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn header_fields_regex_complement() -> TestResult {
+    run(
+        &[
+            CONTACTS,
+            "-d",
+            ",",
+            "--header",
+            "-f",
+            "id",
+            "--fields-regex",
+            "^addr_",
+            "--complement",
+        ],
+        "tests/expected/contacts.csv.header.id.regex.complement.out",
+    )?;
+    run(
+        &[
+            CONTACTS,
+            "-d",
+            ",",
+            "--header",
+            "--mode",
+            "csv",
+            "-f",
+            "id",
+            "--fields-regex",
+            "^addr_",
+            "--complement",
+        ],
+        "tests/expected/contacts.csv.header.id.regex.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn header_per_file() -> TestResult {
+    run(
+        &[CONTACTS, QUOTED, "-d", ",", "--header", "-f", "name"],
+        "tests/expected/header.name.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unknown_column() -> TestResult {
+    dies(
+        &[CONTACTS, "-d", ",", "--header", "-f", "id,mail"],
+        "unknown column \"mail\", available columns: id, name, email, phone, addr_city, addr_zip",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_decreasing_name_range() -> TestResult {
+    dies(
+        &[CONTACTS, "-d", ",", "--header", "-f", "email-id"],
+        "First column in range (email) must come before second column (id)",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_names_without_header() -> TestResult {
+    dies(
+        &[CONTACTS, "-d", ",", "-f", "name"],
+        "illegal list value: \"name\"",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_fields_regex_without_header() -> TestResult {
+    dies(
+        &[CONTACTS, "-d", ",", "--fields-regex", "^addr_"],
+        "--fields-regex needs --header",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_header_chars() -> TestResult {
+    dies(
+        &[CONTACTS, "-c", "1", "--header"],
+        "--header makes sense only with --fields",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn box_f2() -> TestResult {
+    run(
+        &[BOX, "-f", "2", "-d", "│"],
+        "tests/expected/box.txt.f2.dbox.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn box_f3_1_output_delimiter() -> TestResult {
+    run(
+        &[BOX, "-f", "3,1", "-d", "│", "--output-delimiter", " | "],
+        "tests/expected/box.txt.f3,1.dbox.odelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn colons_f2_open() -> TestResult {
+    run(
+        &[COLONS, "-f", "2-", "-d", "::"],
+        "tests/expected/colons.txt.f2-.dcolons.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn df_delimiter_regex() -> TestResult {
+    run(
+        &[DF, "-f", "1,5", "--delimiter-regex", r"\s+"],
+        "tests/expected/df.txt.f1,5.dregex.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn df_delimiter_regex_output_delimiter() -> TestResult {
+    run(
+        &[
+            DF,
+            "-f",
+            "6-",
+            "--delimiter-regex",
+            " +",
+            "--output-delimiter",
+            ",",
+        ],
+        "tests/expected/df.txt.f6-.dregex.odelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_b4() -> TestResult {
+    run_bytes(&[LATIN1, "-b", "4"], "tests/expected/latin1.txt.b4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_b1_4() -> TestResult {
+    run_bytes(&[LATIN1, "-b", "1-4"], "tests/expected/latin1.txt.b1-4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_f1() -> TestResult {
+    run_bytes(&[LATIN1, "-f", "1"], "tests/expected/latin1.txt.f1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_c1_4() -> TestResult {
+    run_bytes(&[LATIN1, "-c", "1-4"], "tests/expected/latin1.txt.c1-4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn books_b1_no_split() -> TestResult {
+    run(&[BOOKS, "-b", "1", "-n"], "tests/expected/books.b1.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn books_b1_2_3_no_split_output_delimiter() -> TestResult {
+    run(
+        &[BOOKS, "-b", "1-2,3", "-n", "--output-delimiter", ":"],
+        "tests/expected/books.b1-2,3.n.odelim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn records_f2() -> TestResult {
+    run_bytes(
+        &[RECORDS, "-z", "-f", "2"],
+        "tests/expected/records.z.f2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn records_f2_only_delimited() -> TestResult {
+    run_bytes(
+        &[RECORDS, "--zero-terminated", "-f", "2", "-s"],
+        "tests/expected/records.z.f2.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn records_f2_csv() -> TestResult {
+    run_bytes(
+        &[RECORDS, "-z", "-f", "2", "--mode", "csv"],
+        "tests/expected/records.z.f2.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn records_b1_3() -> TestResult {
+    run_bytes(
+        &[RECORDS, "-z", "-b", "1-3"],
+        "tests/expected/records.z.b1-3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn records_c2() -> TestResult {
+    run_bytes(
+        &[RECORDS, "-z", "-c", "2"],
+        "tests/expected/records.z.c2.out",
+    )
+}